@@ -0,0 +1,186 @@
+//! Compares the flume channels previously used as worker queues with the
+//! work-stealing deques replacing them, and the resulting spawn throughput.
+
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Barrier,
+	},
+	thread,
+};
+
+use allochronic::{Affinity, Executor, Task};
+use allochronic_channel::{mpmc, oneshot};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_deque::{Steal, Worker};
+use futures_util::{future, StreamExt};
+
+const TASKS: usize = 10_000;
+const THREADS: [usize; 3] = [1, 2, 4];
+
+fn push_pop(c: &mut Criterion) {
+	let mut group = c.benchmark_group("push_pop");
+	group.throughput(Throughput::Elements(TASKS as u64));
+
+	group.bench_function("channel", |b| {
+		let (sender, mut receiver) = mpmc::unbounded();
+
+		b.iter(|| {
+			for task in 0..TASKS {
+				sender.send(task);
+			}
+
+			futures_lite::future::block_on(receiver.clear());
+		});
+	});
+
+	group.bench_function("deque", |b| {
+		let deque = Worker::new_fifo();
+
+		b.iter(|| {
+			for task in 0..TASKS {
+				deque.push(task);
+			}
+
+			while deque.pop().is_some() {}
+		});
+	});
+
+	group.finish();
+}
+
+/// The owner fills it's queue while `thieves` drain it concurrently, stopping
+/// once all tasks were taken.
+fn steal(c: &mut Criterion) {
+	let mut group = c.benchmark_group("steal");
+	group.throughput(Throughput::Elements(TASKS as u64));
+
+	for thieves in THREADS {
+		group.bench_with_input(
+			BenchmarkId::new("channel", thieves),
+			&thieves,
+			|b, &thieves| {
+				b.iter(|| {
+					let (sender, receiver) = mpmc::unbounded();
+					let barrier = Arc::new(Barrier::new(thieves + 1));
+
+					let handles: Vec<_> = (0..thieves)
+						.map(|_| {
+							let mut receiver = receiver.clone();
+							let barrier = Arc::clone(&barrier);
+
+							thread::spawn(move || {
+								barrier.wait();
+								// ends after the sender is dropped and the channel is empty
+								while futures_lite::future::block_on(receiver.next()).is_some() {}
+							})
+						})
+						.collect();
+
+					barrier.wait();
+
+					for task in 0..TASKS {
+						sender.send(task);
+					}
+
+					drop(sender);
+
+					for handle in handles {
+						handle.join().expect("thief panicked");
+					}
+				});
+			},
+		);
+
+		group.bench_with_input(
+			BenchmarkId::new("deque", thieves),
+			&thieves,
+			|b, &thieves| {
+				b.iter(|| {
+					let deque = Worker::new_fifo();
+					let barrier = Arc::new(Barrier::new(thieves + 1));
+					let taken = Arc::new(AtomicUsize::new(0));
+
+					let handles: Vec<_> = (0..thieves)
+						.map(|_| {
+							let stealer = deque.stealer();
+							let barrier = Arc::clone(&barrier);
+							let taken = Arc::clone(&taken);
+
+							thread::spawn(move || {
+								let own = Worker::new_fifo();
+								barrier.wait();
+
+								while taken.load(Ordering::Relaxed) < TASKS {
+									match stealer.steal_batch_and_pop(&own) {
+										Steal::Success(_) => {
+											let mut count = 1;
+
+											while own.pop().is_some() {
+												count += 1;
+											}
+
+											taken.fetch_add(count, Ordering::Relaxed);
+										}
+										// leave the core to the owner, like a blocked receiver
+										Steal::Empty => thread::yield_now(),
+										Steal::Retry => (),
+									}
+								}
+							})
+						})
+						.collect();
+
+					barrier.wait();
+
+					for task in 0..TASKS {
+						deque.push(task);
+					}
+
+					for handle in handles {
+						handle.join().expect("thief panicked");
+					}
+				});
+			},
+		);
+	}
+
+	group.finish();
+}
+
+fn spawn(c: &mut Criterion) {
+	let mut group = c.benchmark_group("spawn");
+	group.throughput(Throughput::Elements(TASKS as u64));
+
+	for workers in THREADS {
+		// an `Executor` can only be started once per thread, keep it running
+		// for all iterations
+		let builder = Executor::builder()
+			.workers(workers)
+			.affinity(Affinity::None);
+		let handle = builder.handle();
+		let (stop, stopped) = oneshot::oneshot::<()>();
+		let executor = thread::spawn(move || {
+			builder.start(async move {
+				let _stopped = stopped.await;
+			});
+		});
+
+		group.bench_with_input(BenchmarkId::new("executor", workers), &workers, |b, _| {
+			b.iter(|| {
+				handle.block_on(async {
+					let tasks: Vec<_> = (0..TASKS).map(|_| Task::spawn(async {})).collect();
+					future::try_join_all(tasks).await.expect("task failed");
+				});
+			});
+		});
+
+		stop.send(());
+		executor.join().expect("executor panicked");
+	}
+
+	group.finish();
+}
+
+criterion_group!(queues, push_pop, steal, spawn);
+criterion_main!(queues);
@@ -8,6 +8,7 @@ use std::{
 	task::{Context, Poll},
 };
 
+use allochronic_util::coop;
 use flume::r#async::RecvStream;
 use futures_util::stream::Stream;
 use parking_lot::RwLock;
@@ -53,6 +54,13 @@ impl<T> Stream for Receiver<T> {
 	type Item = T;
 
 	fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-		Pin::new(&mut self.as_mut().0).poll_next(context)
+		futures_util::ready!(coop::poll_proceed(context));
+		let poll = Pin::new(&mut self.as_mut().0).poll_next(context);
+
+		if poll.is_pending() {
+			coop::refund();
+		}
+
+		poll
 	}
 }
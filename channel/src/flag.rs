@@ -32,6 +32,11 @@ impl Flag {
 		self.0.set.store(true, Ordering::Relaxed);
 		self.0.waker.wake();
 	}
+
+	#[must_use]
+	pub fn is_set(&self) -> bool {
+		self.0.set.load(Ordering::Relaxed)
+	}
 }
 
 impl Default for Flag {
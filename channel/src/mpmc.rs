@@ -4,6 +4,7 @@ use std::{
 	task::{Context, Poll},
 };
 
+use allochronic_util::coop;
 use flume::r#async::RecvStream;
 use futures_util::{stream::Stream, StreamExt};
 
@@ -45,6 +46,11 @@ impl<T> Sender<T> {
 		self.0.send(item).expect("no receiver alive");
 	}
 
+	/// Sends `item`, returning it if no [`Receiver`] is alive.
+	pub fn try_send(&self, item: T) -> Result<(), T> {
+		self.0.send(item).map_err(flume::SendError::into_inner)
+	}
+
 	#[must_use]
 	pub fn is_empty(&self) -> bool {
 		self.0.is_empty()
@@ -58,7 +64,8 @@ impl<T> Sender<T> {
 
 impl<T> Receiver<T> {
 	pub async fn clear(&mut self) {
-		while let Poll::Ready(item) = allochronic_util::poll(self.next()).await {
+		// bypasses the budget to not stop early
+		while let Poll::Ready(item) = allochronic_util::poll(self.0.next()).await {
 			drop(item);
 		}
 	}
@@ -78,6 +85,13 @@ impl<T> Stream for Receiver<T> {
 	type Item = T;
 
 	fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-		Pin::new(&mut self.as_mut().0).poll_next(context)
+		futures_util::ready!(coop::poll_proceed(context));
+		let poll = Pin::new(&mut self.as_mut().0).poll_next(context);
+
+		if poll.is_pending() {
+			coop::refund();
+		}
+
+		poll
 	}
 }
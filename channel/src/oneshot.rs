@@ -4,6 +4,7 @@ use std::{
 	task::{Context, Poll},
 };
 
+use allochronic_util::coop;
 use futures_channel::oneshot;
 use thiserror::Error;
 
@@ -46,8 +47,15 @@ where
 	type Output = Result<T, Canceled>;
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		Pin::new(&mut self.as_mut().0)
+		futures_util::ready!(coop::poll_proceed(cx));
+		let poll = Pin::new(&mut self.as_mut().0)
 			.poll(cx)
-			.map_err(|_canceled| Canceled)
+			.map_err(|_canceled| Canceled);
+
+		if poll.is_pending() {
+			coop::refund();
+		}
+
+		poll
 	}
 }
@@ -0,0 +1,200 @@
+use std::{
+	collections::VecDeque,
+	mem,
+	sync::Arc,
+	thread::{self, JoinHandle},
+	time::Duration,
+};
+
+use allochronic_task::Runnable;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+use crate::{Executor, Task, Worker};
+
+/// Runs `function` on the blocking thread pool of the current [`Executor`],
+/// keeping it from stalling async workers.
+///
+/// Threads are spawned when no idle ones are left, up to
+/// [`Builder::max_blocking_threads`](crate::Builder::max_blocking_threads),
+/// and exit after being idle for
+/// [`Builder::blocking_keep_alive`](crate::Builder::blocking_keep_alive).
+///
+/// # Panics
+/// Panics if not called from a worker.
+pub fn spawn_blocking<F, R>(function: F) -> Task<R>
+where
+	F: FnOnce() -> R + Send + 'static,
+	R: Send + 'static,
+{
+	Task::spawn_blocking_with(
+		Worker::with(|worker| Arc::clone(&worker.executor)),
+		function,
+	)
+}
+
+/// Elastic pool of threads running blocking tasks.
+#[derive(Debug)]
+pub(crate) struct Blocking {
+	state: Mutex<State>,
+	condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+	queue: VecDeque<Runnable>,
+	/// Number of running threads.
+	threads: usize,
+	/// Number of threads waiting for tasks.
+	idle: usize,
+	/// Threads to join on shutdown, including exited ones not cleaned up yet.
+	handles: Vec<JoinHandle<()>>,
+	config: Config,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+	pub(crate) max_threads: usize,
+	pub(crate) keep_alive: Duration,
+	pub(crate) thread_name: String,
+	pub(crate) stack_size: Option<usize>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			max_threads: 512,
+			keep_alive: Duration::from_secs(10),
+			thread_name: String::from("allochronic-worker-blocking"),
+			stack_size: None,
+		}
+	}
+}
+
+impl Blocking {
+	pub(crate) fn new() -> Self {
+		Self {
+			state: Mutex::new(State {
+				queue: VecDeque::new(),
+				threads: 0,
+				idle: 0,
+				handles: Vec::new(),
+				config: Config::default(),
+			}),
+			condvar: Condvar::new(),
+		}
+	}
+
+	pub(crate) fn configure(&self, config: Config) {
+		self.state.lock().config = config;
+	}
+
+	/// Queues `runnable`, spawning a new thread if there are more queued tasks
+	/// than idle threads. If that fails the task is left to running threads,
+	/// or cancelled if there are none.
+	pub(crate) fn schedule(executor: &Arc<Executor>, runnable: Runnable) {
+		// tasks woken up after shutdown are cancelled
+		if executor.is_shutdown() {
+			return;
+		}
+
+		let blocking = &executor.blocking;
+		let mut state = blocking.state.lock();
+		state.queue.push_back(runnable);
+
+		if state.queue.len() > state.idle && state.threads < state.config.max_threads {
+			state.threads += 1;
+			let config = state.config.clone();
+			drop(state);
+
+			let mut thread = thread::Builder::new().name(config.thread_name);
+
+			if let Some(size) = config.stack_size {
+				thread = thread.stack_size(size);
+			}
+
+			let keep_alive = config.keep_alive;
+			let handle = {
+				let executor = Arc::clone(executor);
+				thread.spawn(move || Self::run(executor, keep_alive))
+			};
+
+			let mut state = blocking.state.lock();
+
+			match handle {
+				Ok(handle) => {
+					state.handles.retain(|handle| !handle.is_finished());
+					state.handles.push(handle);
+				}
+				Err(_error) => {
+					#[cfg(feature = "tracing")]
+					tracing::warn!(error = %_error, "failed to spawn blocking thread");
+					state.threads -= 1;
+
+					if state.threads == 0 {
+						// dropping a `Runnable` cancels it's task
+						let queue = mem::take(&mut state.queue);
+						drop(state);
+						drop(queue);
+					} else {
+						// hand the task to an existing thread instead
+						drop(state);
+						blocking.condvar.notify_one();
+					}
+				}
+			}
+		} else {
+			drop(state);
+			blocking.condvar.notify_one();
+		}
+	}
+
+	fn run(executor: Arc<Executor>, keep_alive: Duration) {
+		Worker::init_blocking(Arc::clone(&executor));
+
+		let blocking = &executor.blocking;
+		let mut state = blocking.state.lock();
+
+		loop {
+			if let Some(runnable) = state.queue.pop_front() {
+				MutexGuard::unlocked(&mut state, || {
+					runnable.run();
+				});
+				continue;
+			}
+
+			if executor.is_shutdown() {
+				break;
+			}
+
+			state.idle += 1;
+			let timeout = blocking.condvar.wait_for(&mut state, keep_alive);
+			state.idle -= 1;
+
+			if timeout.timed_out() && state.queue.is_empty() {
+				break;
+			}
+		}
+
+		state.threads -= 1;
+	}
+
+	/// Cancels all queued tasks and stops idle threads, running tasks are
+	/// finished first.
+	pub(crate) fn shutdown(&self) {
+		let queue = mem::take(&mut self.state.lock().queue);
+		self.condvar.notify_all();
+
+		// dropping a `Runnable` cancels it's task
+		drop(queue);
+	}
+
+	/// Waits for all threads to exit after [`shutdown`](Self::shutdown).
+	pub(crate) fn join(&self) {
+		let handles = mem::take(&mut self.state.lock().handles);
+
+		for handle in handles {
+			// panics of tasks are caught and returned by awaiting them
+			drop(handle.join());
+		}
+	}
+}
@@ -0,0 +1,286 @@
+use std::{future::Future, sync::Arc, thread, time::Duration};
+
+use core_affinity::CoreId;
+use parking_lot::Mutex;
+
+use crate::{blocking, error, Executor, Handle};
+
+/// Configures and starts an [`Executor`].
+#[derive(Clone, Debug)]
+pub struct Builder {
+	/// Created by [`handle`](Self::handle) and shared between clones, the
+	/// first one started takes it.
+	executor: Arc<Mutex<Option<Arc<Executor>>>>,
+	workers: Option<usize>,
+	affinity: Affinity,
+	thread_name: String,
+	stack_size: Option<usize>,
+	abort_on_panic: bool,
+	idle: Idle,
+	steal: StealPolicy,
+	injector_interval: u32,
+	max_blocking_threads: usize,
+	blocking_keep_alive: Duration,
+}
+
+/// Controls how worker threads are pinned to cores.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Affinity {
+	/// Pin each worker to one of the cores available to the process.
+	All,
+	/// Pin workers to the given core ids, cycling through them if there are
+	/// more workers than cores.
+	Cores(Vec<usize>),
+	/// Don't pin workers.
+	None,
+}
+
+/// Controls how workers wait when they run out of tasks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Idle {
+	/// Never park, keep polling for tasks and I/O events. Lowest latency, but
+	/// keeps all cores busy.
+	BusyPoll,
+	/// Poll for tasks the given number of times before parking.
+	SpinThenPark(u32),
+	/// Park as soon as no tasks are left.
+	Park,
+}
+
+impl Default for Idle {
+	fn default() -> Self {
+		Self::SpinThenPark(64)
+	}
+}
+
+/// Controls which workers an idle worker steals tasks from first. It takes
+/// up to half of the tasks queued by the first worker that has any.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StealPolicy {
+	/// Start with a random worker every time.
+	Random,
+	/// Start with the next worker every time.
+	RoundRobin,
+	/// Start with workers pinned to the closest cores: sharing the lowest
+	/// level cache, then the same NUMA node, as reported by Linux. Falls back
+	/// to the closest worker indices if workers aren't pinned, see
+	/// [`Affinity`], or on other platforms.
+	NearFirst,
+}
+
+// implemented like `Idle`, which can't derive it
+#[allow(clippy::derivable_impls)]
+impl Default for StealPolicy {
+	fn default() -> Self {
+		Self::Random
+	}
+}
+
+impl Default for Builder {
+	fn default() -> Self {
+		Self {
+			executor: Arc::default(),
+			workers: None,
+			affinity: Affinity::All,
+			thread_name: String::from("allochronic-worker"),
+			stack_size: None,
+			abort_on_panic: false,
+			idle: Idle::default(),
+			steal: StealPolicy::default(),
+			injector_interval: 61,
+			max_blocking_threads: 512,
+			blocking_keep_alive: Duration::from_secs(10),
+		}
+	}
+}
+
+impl Builder {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the number of workers, including the thread calling
+	/// [`start`](Self::start). Defaults to the number of available cores.
+	#[must_use]
+	pub fn workers(mut self, workers: usize) -> Self {
+		assert_ne!(workers, 0, "`Executor` needs at least one worker");
+		self.workers = Some(workers);
+		self
+	}
+
+	/// Sets how workers are pinned to cores. Defaults to [`Affinity::All`].
+	#[must_use]
+	pub fn affinity(mut self, affinity: Affinity) -> Self {
+		self.affinity = affinity;
+		self
+	}
+
+	/// Sets the prefix of worker thread names, followed by the worker index.
+	/// Defaults to `allochronic-worker`.
+	#[must_use]
+	pub fn thread_name<N: Into<String>>(mut self, prefix: N) -> Self {
+		self.thread_name = prefix.into();
+		self
+	}
+
+	/// Sets the stack size of spawned worker threads.
+	#[must_use]
+	pub const fn stack_size(mut self, size: usize) -> Self {
+		self.stack_size = Some(size);
+		self
+	}
+
+	/// Aborts the process if a spawned [`Task`](crate::Task) panics, instead
+	/// of returning the panic when awaiting it. Defaults to `false`.
+	#[must_use]
+	pub const fn abort_on_panic(mut self, abort: bool) -> Self {
+		self.abort_on_panic = abort;
+		self
+	}
+
+	/// Sets how workers wait when they run out of tasks. Defaults to
+	/// [`Idle::SpinThenPark`] with `64` spins.
+	#[must_use]
+	pub const fn idle(mut self, idle: Idle) -> Self {
+		self.idle = idle;
+		self
+	}
+
+	/// Sets which workers are stolen from first. Defaults to
+	/// [`StealPolicy::Random`].
+	#[must_use]
+	pub const fn steal_policy(mut self, steal: StealPolicy) -> Self {
+		self.steal = steal;
+		self
+	}
+
+	/// Sets after how many tasks workers check the global injectors first,
+	/// before their own queues. Tasks spawned from outside of workers are
+	/// sent to the global injectors, this makes sure they run even if workers
+	/// always have tasks of their own. Defaults to `61`.
+	#[must_use]
+	pub fn injector_interval(mut self, interval: u32) -> Self {
+		assert_ne!(interval, 0, "injector interval has to be at least one");
+		self.injector_interval = interval;
+		self
+	}
+
+	/// Sets the maximum number of threads running
+	/// [`spawn_blocking`](crate::spawn_blocking) tasks. Defaults to `512`.
+	#[must_use]
+	pub fn max_blocking_threads(mut self, threads: usize) -> Self {
+		assert_ne!(threads, 0, "blocking pool needs at least one thread");
+		self.max_blocking_threads = threads;
+		self
+	}
+
+	/// Sets how long idle blocking threads wait for new tasks before exiting.
+	/// Defaults to 10 seconds.
+	#[must_use]
+	pub const fn blocking_keep_alive(mut self, keep_alive: Duration) -> Self {
+		self.blocking_keep_alive = keep_alive;
+		self
+	}
+
+	/// Returns a [`Handle`] to the [`Executor`] that will be started by this
+	/// [`Builder`] or the first of it's clones to be started.
+	/// [`Task`](crate::Task)s spawned through it before the [`Executor`] is
+	/// started are queued until it is.
+	#[must_use]
+	pub fn handle(&self) -> Handle {
+		Handle::new(Arc::clone(
+			self.executor.lock().get_or_insert_with(Executor::new),
+		))
+	}
+
+	pub fn start<M, R>(self, main: M) -> R
+	where
+		M: Future<Output = R>,
+	{
+		self.try_start(main).expect("`Executor::start` failed")
+	}
+
+	pub fn try_start<M, R>(self, main: M) -> Result<R, error::Executor>
+	where
+		M: Future<Output = R>,
+	{
+		let executor = self.executor.lock().take().unwrap_or_else(Executor::new);
+
+		Executor::run(&self, executor, main)
+	}
+
+	pub(crate) fn thread(&self, index: usize) -> thread::Builder {
+		let thread = thread::Builder::new().name(format!("{}-{}", self.thread_name, index));
+
+		match self.stack_size {
+			Some(size) => thread.stack_size(size),
+			None => thread,
+		}
+	}
+
+	pub(crate) const fn aborts_on_panic(&self) -> bool {
+		self.abort_on_panic
+	}
+
+	pub(crate) const fn idle_strategy(&self) -> Idle {
+		self.idle
+	}
+
+	pub(crate) const fn steal(&self) -> StealPolicy {
+		self.steal
+	}
+
+	pub(crate) const fn injector_ticks(&self) -> u32 {
+		self.injector_interval
+	}
+
+	pub(crate) fn blocking(&self) -> blocking::Config {
+		blocking::Config {
+			max_threads: self.max_blocking_threads,
+			keep_alive: self.blocking_keep_alive,
+			thread_name: format!("{}-blocking", self.thread_name),
+			stack_size: self.stack_size,
+		}
+	}
+
+	pub(crate) fn cores(&self) -> Vec<Option<CoreId>> {
+		let available = || {
+			core_affinity::get_core_ids()
+				.filter(|cores| !cores.is_empty())
+				.map(|cores| cores.into_iter().map(|core| core.id).collect::<Vec<_>>())
+		};
+
+		let cores = match &self.affinity {
+			Affinity::All => available(),
+			Affinity::Cores(cores) if !cores.is_empty() => Some(cores.clone()),
+			Affinity::Cores(_) | Affinity::None => None,
+		};
+
+		let workers = self.workers.unwrap_or_else(|| {
+			cores.as_ref().map_or_else(
+				|| {
+					available().map_or_else(
+						|| match num_cpus::get_physical() {
+							0 => unreachable!("no cores found"),
+							cores => cores,
+						},
+						|cores| cores.len(),
+					)
+				},
+				Vec::len,
+			)
+		});
+
+		if let Some(cores) = cores {
+			cores
+				.into_iter()
+				.cycle()
+				.take(workers)
+				.map(|id| Some(CoreId { id }))
+				.collect()
+		} else {
+			vec![None; workers]
+		}
+	}
+}
@@ -0,0 +1,21 @@
+//! Cooperative scheduling, making long running [`Task`](crate::Task)s yield
+//! back to the worker so they don't starve others.
+//!
+//! Every time a worker polls a task, it gets a budget of operations.
+//! Allochronic's channels consume it whenever they're ready, and once it's
+//! exhausted make the task yield, like
+//! [`r#yield`](allochronic_util::r#yield) would.
+
+use futures_util::future;
+
+/// Operations a task can do each time it's polled.
+pub(crate) const BUDGET: u8 = 128;
+
+/// Consumes one operation of the current task's budget, yielding to the
+/// worker if it's exhausted.
+///
+/// Useful in loops that don't use allochronic's channels, which consume
+/// budget already.
+pub async fn consume_budget() {
+	future::poll_fn(allochronic_util::coop::poll_proceed).await;
+}
@@ -1,7 +1,43 @@
+use std::any::Any;
+
 use thiserror::Error;
 
-#[derive(Clone, Copy, Debug, Error)]
+#[derive(Debug, Error)]
 pub enum Executor {
 	#[error("Executor was shutdown before finishing")]
 	Cancelled,
+	#[error("{} worker(s) panicked", .0.len())]
+	Panicked(Vec<WorkerPanic>),
+}
+
+/// Error returned by awaiting a [`Task`](crate::Task).
+#[derive(Debug, Error)]
+pub enum Join {
+	#[error("`Task` panicked")]
+	Panic(Box<dyn Any + Send>),
+	#[error("`Task` was cancelled")]
+	Cancelled,
+}
+
+/// Error returned by [`timeout`](crate::time::timeout) if the future didn't
+/// finish in time.
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, Ord, PartialEq, PartialOrd)]
+#[error("deadline has elapsed")]
+pub struct Elapsed;
+
+/// Error returned by [`LocalKey::try_with`](crate::task::LocalKey::try_with)
+/// if called outside of it's scope.
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, Ord, PartialEq, PartialOrd)]
+#[error("task-local value accessed outside of it's scope")]
+pub struct Access;
+
+/// Panic that caused a worker thread to exit.
+#[derive(Debug)]
+pub struct WorkerPanic {
+	/// Index of the worker. Never `0`, the thread that started the
+	/// [`Executor`](crate::Executor), panics there unwind out of
+	/// [`Builder::start`](crate::Builder::start) instead.
+	pub index: usize,
+	/// Payload passed to [`panic!`].
+	pub payload: Box<dyn Any + Send>,
 }
@@ -1,64 +1,172 @@
 use std::{
 	future::Future,
 	iter::FromIterator,
+	mem,
+	pin::Pin,
 	sync::{
-		atomic::{AtomicUsize, Ordering},
+		atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
 		Arc,
 	},
+	task::{Context, Poll},
 	thread,
+	time::Duration,
 };
 
-use allochronic_channel::{broadcast, flag::Flag, mpmc, notify::Notify, oneshot};
+use allochronic_channel::{broadcast, flag::Flag, mpmc, oneshot};
 use allochronic_task::Runnable;
+use futures_util::{task::AtomicWaker, Stream, StreamExt};
 use parking_lot::{Mutex, RwLock};
 #[cfg(feature = "tokio-support")]
 use tokio::runtime::Runtime;
 use vec_map::VecMap;
 
-use crate::{error, Worker};
+use crate::{
+	blocking::Blocking,
+	error,
+	metrics::{Counters, Metrics},
+	reactor::Reactor,
+	time,
+	time::Timers,
+	topology::Core,
+	worker::Sleepers,
+	Builder, Idle, StealPolicy, Worker,
+};
 
 type Sender = mpmc::Sender<Runnable>;
 type Receiver = mpmc::Receiver<Runnable>;
+type Deque = crossbeam_deque::Worker<Runnable>;
+type Stealer = crossbeam_deque::Stealer<Runnable>;
 
 #[derive(Debug)]
 pub struct Executor {
 	pub(crate) tasks: AtomicUsize,
-	threads: Mutex<Vec<oneshot::Receiver<thread::Result<()>>>>,
+	spawned: AtomicU64,
+	/// Id of the next task, `main` takes one without being spawned.
+	next_id: AtomicU64,
+	closed: AtomicBool,
+	abort_on_panic: AtomicBool,
+	idle: Mutex<Idle>,
+	steal: Mutex<StealPolicy>,
+	injector_interval: AtomicU32,
+	/// Core each worker is pinned to, if it's topology is known.
+	cores: RwLock<Vec<Option<(usize, Core)>>>,
+	threads: Mutex<Vec<(usize, oneshot::Receiver<thread::Result<()>>)>>,
 	pub(crate) shutdown: Flag,
-	pub(crate) finished: Notify,
 	management: broadcast::Sender<()>,
-	pub(crate) injector: RwLock<VecMap<VecMap<(Sender, Receiver)>>>,
+	/// Live [`TaskCounts`] streams, notified when the task count changes.
+	watchers: RwLock<Vec<Arc<Watcher>>>,
+	/// Number of `watchers`, to not lock on every spawn if there are none.
+	watched: AtomicUsize,
+	workers: AtomicUsize,
+	/// Metric counters of each worker.
+	counters: RwLock<Vec<Arc<Counters>>>,
+	/// Queues by priority and group.
+	pub(crate) queues: RwLock<VecMap<VecMap<Channels>>>,
+	/// Workers serving a group, groups not present are served by all workers.
+	groups: RwLock<VecMap<Vec<usize>>>,
+	next_group: AtomicUsize,
+	/// Ids of removed groups, reused by new ones.
+	free_groups: Mutex<Vec<usize>>,
+	pub(crate) blocking: Blocking,
+	pub(crate) timers: Timers,
+	pub(crate) reactor: Reactor,
+	/// Parked workers, woken up one at a time.
+	pub(crate) sleepers: Sleepers,
 	#[cfg(feature = "tokio-support")]
 	pub(crate) tokio: Runtime,
 }
 
+/// Stream returned by [`Executor::task_counts`].
+///
+/// Only the latest count is kept, counts that changed again before the
+/// stream was polled are skipped.
+#[derive(Debug)]
+pub struct TaskCounts {
+	executor: Arc<Executor>,
+	watcher: Arc<Watcher>,
+}
+
+#[derive(Debug, Default)]
+struct Watcher {
+	/// The count changed since it was last yielded.
+	changed: AtomicBool,
+	waker: AtomicWaker,
+}
+
+impl Stream for TaskCounts {
+	type Item = usize;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.watcher.waker.register(cx.waker());
+
+		if self.watcher.changed.swap(false, Ordering::SeqCst) {
+			Poll::Ready(Some(self.executor.tasks.load(Ordering::SeqCst)))
+		} else {
+			Poll::Pending
+		}
+	}
+}
+
+impl Drop for TaskCounts {
+	fn drop(&mut self) {
+		let mut watchers = self.executor.watchers.write();
+		watchers.retain(|watcher| !Arc::ptr_eq(watcher, &self.watcher));
+		self.executor
+			.watched
+			.store(watchers.len(), Ordering::SeqCst);
+	}
+}
+
+/// Global injector and worker queues of a single priority and group.
+#[derive(Debug)]
+pub(crate) struct Channels {
+	pub(crate) injector: (Sender, Receiver),
+	/// Work-stealing queue of each worker, taken by the worker when it picks
+	/// up the queues, and it's [`Stealer`] shared with all other workers.
+	pub(crate) workers: Vec<(Mutex<Option<Deque>>, Stealer)>,
+}
+
+impl Channels {
+	fn new(workers: usize) -> Self {
+		Self {
+			injector: mpmc::unbounded(),
+			workers: Self::deques(workers),
+		}
+	}
+
+	fn deques(workers: usize) -> Vec<(Mutex<Option<Deque>>, Stealer)> {
+		(0..workers)
+			.map(|_| {
+				let deque = Deque::new_fifo();
+				let stealer = deque.stealer();
+
+				(Mutex::new(Some(deque)), stealer)
+			})
+			.collect()
+	}
+}
+
 impl Executor {
+	#[must_use]
+	pub fn builder() -> Builder {
+		Builder::new()
+	}
+
 	pub fn start<M, R>(main: M) -> R
 	where
 		M: Future<Output = R>,
 	{
-		Self::try_start(main).expect("`Executor::start` failed")
+		Self::builder().start(main)
 	}
 
-	#[allow(clippy::panic_in_result_fn, clippy::unwrap_in_result)]
 	pub fn try_start<M, R>(main: M) -> Result<R, error::Executor>
 	where
 		M: Future<Output = R>,
 	{
-		let cores = match core_affinity::get_core_ids() {
-			Some(cores) if !cores.is_empty() => cores.into_iter().map(Some).collect(),
-			_ => {
-				let cores = match num_cpus::get_physical() {
-					0 => unreachable!("no cores found"),
-					cores => cores,
-				};
-				vec![None; cores]
-			}
-		};
-
-		let injector = mpmc::unbounded();
-		let injector_receiver = injector.1.clone();
+		Self::builder().try_start(main)
+	}
 
+	pub(crate) fn new() -> Arc<Self> {
 		// add tokio support
 		#[cfg(feature = "tokio-support")]
 		let tokio = tokio::runtime::Builder::new_multi_thread()
@@ -66,87 +174,462 @@ impl Executor {
 			.build()
 			.expect("failed to build tokio `Runtime`");
 
-		let executor = Arc::new(Self {
+		Arc::new(Self {
 			tasks: AtomicUsize::new(0),
+			spawned: AtomicU64::new(0),
+			next_id: AtomicU64::new(0),
+			closed: AtomicBool::new(false),
+			abort_on_panic: AtomicBool::new(false),
+			idle: Mutex::new(Idle::default()),
+			steal: Mutex::new(StealPolicy::default()),
+			injector_interval: AtomicU32::new(61),
+			cores: RwLock::default(),
 			threads: Mutex::default(),
 			shutdown: Flag::new(),
-			finished: Notify::new(),
 			management: broadcast::unbounded(),
-			injector: RwLock::new(VecMap::from_iter(Some((
+			watchers: RwLock::default(),
+			watched: AtomicUsize::new(0),
+			workers: AtomicUsize::new(0),
+			counters: RwLock::default(),
+			queues: RwLock::new(VecMap::from_iter(Some((
 				0,
-				VecMap::from_iter(Some((0, injector))),
+				VecMap::from_iter(Some((0, Channels::new(0)))),
 			)))),
+			groups: RwLock::default(),
+			next_group: AtomicUsize::new(1),
+			free_groups: Mutex::default(),
+			blocking: Blocking::new(),
+			timers: Timers::default(),
+			reactor: Reactor::new().expect("failed to create reactor"),
+			sleepers: Sleepers::default(),
 			#[cfg(feature = "tokio-support")]
 			tokio,
-		});
+		})
+	}
 
-		let threads: Vec<_> = {
-			let queues: Vec<_> = (0..cores.len()).map(|_| mpmc::unbounded()).collect();
+	#[allow(clippy::panic_in_result_fn, clippy::unwrap_in_result)]
+	pub(crate) fn run<M, R>(
+		builder: &Builder,
+		executor: Arc<Self>,
+		main: M,
+	) -> Result<R, error::Executor>
+	where
+		M: Future<Output = R>,
+	{
+		let cores = builder.cores();
+		executor
+			.abort_on_panic
+			.store(builder.aborts_on_panic(), Ordering::Relaxed);
+		*executor.idle.lock() = builder.idle_strategy();
+		*executor.steal.lock() = builder.steal();
+		executor
+			.injector_interval
+			.store(builder.injector_ticks(), Ordering::Relaxed);
+		*executor.cores.write() = cores
+			.iter()
+			.map(|core| core.and_then(|core| Some((core.id, Core::read(core.id)?))))
+			.collect();
+		executor.blocking.configure(builder.blocking());
 
-			cores
-				.into_iter()
-				.zip(queues.clone())
-				.enumerate()
-				.map(|(index, (core, (sender, receiver)))| {
-					let stealer: Vec<_> = queues
-						.iter()
-						.enumerate()
-						.filter(|(queue, _)| *queue != index)
-						.map(|(_, (_, receiver))| receiver.clone())
-						.collect();
-
-					(core, sender, receiver, stealer)
-				})
-				.collect()
-		};
-
-		let mut threads = threads.into_iter();
-		let main_thread = threads.next().expect("no main thread found");
-
-		for (core, sender, receiver, stealer) in threads {
+		executor.start_workers(cores.len());
+
+		let mut cores = cores.into_iter().enumerate();
+		let (_, main_core) = cores.next().expect("no main thread found");
+
+		for (index, core) in cores {
 			let handle = {
 				let executor = Arc::clone(&executor);
-				let injector = injector_receiver.clone();
 
-				thread::spawn(move || {
-					Worker::start(executor, core, sender, receiver, injector, stealer);
-				})
+				builder
+					.thread(index)
+					.spawn(move || Worker::start(executor, index, core))
+					.expect("failed to spawn worker thread")
 			};
 
 			let (sender, receiver) = oneshot::oneshot();
-			executor.threads.lock().push(receiver);
+			executor.threads.lock().push((index, receiver));
 
 			thread::spawn(move || {
 				sender.send(handle.join());
 			});
 		}
 
-		let (core, sender, receiver, stealer) = main_thread;
+		let result = Worker::start_with(Arc::clone(&executor), 0, main_core, main);
+
+		// returning from `main` stops all other workers
+		let _cancelled = executor.signal();
+		let panics = executor.join();
+
+		if panics.is_empty() {
+			result
+		} else {
+			Err(error::Executor::Panicked(panics))
+		}
+	}
+
+	/// Creates the worker queues of all priorities and groups registered so
+	/// far, further ones are created by [`register`](Self::register).
+	fn start_workers(&self, workers: usize) {
+		let mut queues = self.queues.write();
+		self.workers.store(workers, Ordering::SeqCst);
+		*self.counters.write() = (0..workers).map(|_| Arc::default()).collect();
 
-		Worker::start_with(
-			executor,
-			core,
-			sender,
-			receiver,
-			injector_receiver,
-			stealer,
-			main,
+		for channels in queues.values_mut().flat_map(VecMap::values_mut) {
+			channels.workers = Channels::deques(workers);
+		}
+	}
+
+	/// Makes sure queues for `priority` and `group` exist, notifying all
+	/// workers if they had to be created.
+	pub(crate) fn register(&self, priority: usize, group: usize) {
+		if self
+			.queues
+			.read()
+			.get(priority)
+			.map_or(false, |groups| groups.contains_key(group))
+		{
+			return;
+		}
+
+		let mut queues = self.queues.write();
+		let groups = queues.entry(priority).or_insert_with(VecMap::new);
+
+		if !groups.contains_key(group) {
+			groups.insert(group, Channels::new(self.workers.load(Ordering::SeqCst)));
+			drop(queues);
+
+			// let workers pick up the new queues
+			self.management.send(());
+		}
+	}
+
+	pub(crate) fn workers(&self) -> usize {
+		self.workers.load(Ordering::SeqCst)
+	}
+
+	/// Returns the metric counters of worker `index`.
+	pub(crate) fn counters(&self, index: usize) -> Arc<Counters> {
+		Arc::clone(
+			self.counters
+				.read()
+				.get(index)
+				.expect("worker counters not found"),
 		)
 	}
 
+	/// Reserves a new group, optionally only served by the given `workers`.
+	pub(crate) fn create_group(&self, workers: Option<Vec<usize>>) -> usize {
+		let group = self
+			.free_groups
+			.lock()
+			.pop()
+			.unwrap_or_else(|| self.next_group.fetch_add(1, Ordering::SeqCst));
+
+		if let Some(workers) = workers {
+			self.groups.write().insert(group, workers);
+		}
+
+		self.register(0, group);
+
+		group
+	}
+
+	/// Removes the queues of `group`, making it's id available to new groups.
+	/// Workers drop their queues of it when picking up the change.
+	pub(crate) fn remove_group(&self, group: usize) {
+		let mut queues = self.queues.write();
+
+		for groups in queues.values_mut() {
+			drop(groups.remove(group));
+		}
+
+		queues.retain(|_, groups| !groups.is_empty());
+		drop(queues);
+
+		drop(self.groups.write().remove(group));
+		self.free_groups.lock().push(group);
+
+		// let workers drop their queues
+		self.management.send(());
+	}
+
+	/// Checks if `worker` takes tasks from `group`.
+	pub(crate) fn serves(&self, group: usize, worker: usize) -> bool {
+		self.groups
+			.read()
+			.get(group)
+			.map_or(true, |workers| workers.contains(&worker))
+	}
+
+	/// Sends `runnable` to the global injector of `priority` and `group`.
+	pub(crate) fn inject(&self, priority: usize, group: usize, runnable: Runnable) {
+		self.queues
+			.read()
+			.get(priority)
+			.and_then(|groups| groups.get(group))
+			.expect("queue not registered")
+			.injector
+			.0
+			.send(runnable);
+	}
+
+	/// Waits for all worker and blocking threads to exit, returning the
+	/// panics of workers.
+	fn join(&self) -> Vec<error::WorkerPanic> {
+		let threads = mem::take(&mut *self.threads.lock());
+
+		let panics = threads
+			.into_iter()
+			.filter_map(|(index, thread)| {
+				futures_lite::future::block_on(thread)
+					.expect("thread joining worker panicked")
+					.err()
+					.map(|payload| error::WorkerPanic { index, payload })
+			})
+			.collect();
+
+		// workers could still spawn blocking threads until they exited
+		self.blocking.join();
+
+		panics
+	}
+
 	pub(crate) fn management(&self) -> broadcast::Receiver<()> {
 		self.management.subscribe()
 	}
 
+	/// Wakes up a parked worker, for example to let it pick up an earlier
+	/// timer.
+	pub(crate) fn wake(&self) {
+		self.sleepers.wake_one();
+	}
+
+	/// Waits for all tasks to finish.
+	///
+	/// A [`Task`](crate::Task) calling this counts itself, so it has to be
+	/// called from `main` or [`Task::block_on`](crate::Task::block_on) to ever
+	/// return.
 	pub async fn wait() {
 		let executor = Worker::with(|worker| Arc::clone(&worker.executor));
+		executor.quiesce(None).await;
+	}
+
+	/// Waits up to `timeout` for all tasks to finish. Returns `true` if they
+	/// did. Like [`wait`](Self::wait), a [`Task`](crate::Task) calling this
+	/// counts itself.
+	pub async fn wait_timeout(timeout: Duration) -> bool {
+		let executor = Worker::with(|worker| Arc::clone(&worker.executor));
+		executor.quiesce(Some(timeout)).await
+	}
+
+	/// Waits until no tasks are left or `timeout` elapsed. Returns `true` if
+	/// no tasks are left.
+	async fn quiesce(self: &Arc<Self>, timeout: Option<Duration>) -> bool {
+		let mut deadline = timeout.map(time::sleep);
+		// every waiter gets it's own watcher, subscribed before checking the count
+		// to not miss the last task finishing
+		let mut counts = self.subscribe_counts();
 
 		loop {
-			if executor.tasks.load(Ordering::SeqCst) == 0 {
-				break;
+			if self.tasks.load(Ordering::SeqCst) == 0 {
+				break true;
 			}
 
-			(&executor.finished).await;
+			if let Some(deadline) = &mut deadline {
+				if allochronic_util::select!(
+					_: deadline => true,
+					_: &mut counts => false,
+				) {
+					break false;
+				}
+			} else {
+				counts.next().await;
+			}
+		}
+	}
+
+	/// Returns the number of tasks that didn't finish yet.
+	#[must_use]
+	pub fn active_tasks() -> usize {
+		Worker::with(|worker| worker.executor.tasks.load(Ordering::SeqCst))
+	}
+
+	/// Returns a [`Stream`](futures_util::Stream) yielding the number of
+	/// tasks that didn't finish yet when it changed, see [`TaskCounts`].
+	#[must_use]
+	pub fn task_counts() -> TaskCounts {
+		Worker::with(|worker| worker.executor.subscribe_counts())
+	}
+
+	pub(crate) fn subscribe_counts(self: &Arc<Self>) -> TaskCounts {
+		let watcher = Arc::new(Watcher::default());
+
+		let mut watchers = self.watchers.write();
+		watchers.push(Arc::clone(&watcher));
+		self.watched.store(watchers.len(), Ordering::SeqCst);
+
+		TaskCounts {
+			executor: Arc::clone(self),
+			watcher,
+		}
+	}
+
+	/// Returns a snapshot of the runtime statistics of the [`Executor`].
+	#[must_use]
+	pub fn metrics() -> Metrics {
+		Worker::with(|worker| worker.executor.snapshot())
+	}
+
+	pub(crate) fn snapshot(&self) -> Metrics {
+		let queues = self.queues.read();
+		let channels = || queues.values().flat_map(VecMap::values);
+
+		Metrics {
+			spawned: self.spawned.load(Ordering::Relaxed),
+			alive: self.tasks.load(Ordering::SeqCst),
+			injected: channels().map(|channels| channels.injector.1.len()).sum(),
+			workers: self
+				.counters
+				.read()
+				.iter()
+				.enumerate()
+				.map(|(index, counters)| counters.snapshot(index, channels()))
+				.collect(),
 		}
 	}
+
+	/// Shuts down the [`Executor`] immediately, cancelling all unfinished
+	/// tasks. Returns the number of tasks cancelled.
+	pub fn shutdown() -> usize {
+		Worker::with(|worker| Arc::clone(&worker.executor)).signal()
+	}
+
+	/// Stops accepting new tasks and waits up to `timeout` for running tasks
+	/// to finish before shutting down. Returns the number of tasks cancelled.
+	///
+	/// This is meant to be called from `main`, a spawned [`Task`](crate::Task)
+	/// calling this will wait for itself and always run into `timeout`.
+	pub async fn shutdown_graceful(timeout: Duration) -> usize {
+		let executor = Worker::with(|worker| Arc::clone(&worker.executor));
+		executor.closed.store(true, Ordering::SeqCst);
+		#[cfg(feature = "tracing")]
+		tracing::debug!(?timeout, "shutting down gracefully");
+
+		let _idle = executor.quiesce(Some(timeout)).await;
+
+		executor.signal()
+	}
+
+	pub(crate) fn is_closed(&self) -> bool {
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	pub(crate) fn aborts_on_panic(&self) -> bool {
+		self.abort_on_panic.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn idle(&self) -> Idle {
+		*self.idle.lock()
+	}
+
+	pub(crate) fn steal(&self) -> StealPolicy {
+		*self.steal.lock()
+	}
+
+	pub(crate) fn injector_interval(&self) -> u32 {
+		self.injector_interval.load(Ordering::Relaxed)
+	}
+
+	/// Returns how far apart workers `from` and `to` are, by the caches and
+	/// NUMA nodes of the cores they are pinned to, or their indices if that's
+	/// unknown.
+	pub(crate) fn distance(&self, from: usize, to: usize) -> usize {
+		let cores = self.cores.read();
+		let core = |index: usize| cores.get(index).and_then(Option::as_ref);
+
+		if let (Some((_, from_core)), Some((to_id, to_core))) = (core(from), core(to)) {
+			from_core.distance(*to_id, to_core)
+		} else {
+			from.abs_diff(to)
+		}
+	}
+
+	pub(crate) fn is_shutdown(&self) -> bool {
+		self.shutdown.is_set()
+	}
+
+	pub(crate) fn track(self: &Arc<Self>) -> Tracker {
+		self.tasks.fetch_add(1, Ordering::SeqCst);
+		self.spawned.fetch_add(1, Ordering::Relaxed);
+		self.count_changed();
+
+		Tracker {
+			executor: Arc::clone(self),
+			id: self.next_id(),
+		}
+	}
+
+	/// Notifies [`TaskCounts`] streams, only if there are any to not lock on
+	/// every spawn.
+	fn count_changed(&self) {
+		if self.watched.load(Ordering::Relaxed) != 0 {
+			for watcher in &*self.watchers.read() {
+				watcher.changed.store(true, Ordering::SeqCst);
+				watcher.waker.wake();
+			}
+		}
+	}
+
+	/// Returns a new task id.
+	pub(crate) fn next_id(&self) -> u64 {
+		self.next_id.fetch_add(1, Ordering::Relaxed)
+	}
+
+	fn signal(&self) -> usize {
+		#[cfg(feature = "tracing")]
+		tracing::debug!(tasks = self.tasks.load(Ordering::SeqCst), "shutting down");
+
+		self.closed.store(true, Ordering::SeqCst);
+		self.shutdown.signal();
+		// `Flag` only wakes up the last registered worker
+		self.management.send(());
+		self.blocking.shutdown();
+
+		self.tasks.load(Ordering::SeqCst)
+	}
+}
+
+/// Keeps track of a running task, decrementing the task count when dropped,
+/// either by finishing or by being cancelled.
+#[derive(Debug)]
+pub(crate) struct Tracker {
+	executor: Arc<Executor>,
+	/// Unique per [`Executor`].
+	id: u64,
+}
+
+impl Tracker {
+	pub(crate) const fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// Polls `future` in the span of the tracked task, logging `event` in it.
+	#[cfg(feature = "tracing")]
+	pub(crate) fn instrument<F>(
+		&self,
+		name: Option<&str>,
+		event: &str,
+		future: F,
+	) -> tracing::instrument::Instrumented<F> {
+		let span = tracing::trace_span!("task", id = self.id, name);
+		span.in_scope(|| tracing::trace!("{}", event));
+		tracing::Instrument::instrument(future, span)
+	}
+}
+
+impl Drop for Tracker {
+	fn drop(&mut self) {
+		self.executor.tasks.fetch_sub(1, Ordering::SeqCst);
+		self.executor.count_changed();
+	}
 }
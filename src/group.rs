@@ -0,0 +1,150 @@
+use std::{
+	collections::HashMap,
+	future::Future,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+};
+
+use futures_util::future::{AbortHandle, Abortable};
+use parking_lot::Mutex;
+
+use crate::{error, Executor, Task, Worker};
+
+/// Group of [`Task`]s with their own queues, optionally only served by some
+/// workers.
+#[derive(Clone, Debug)]
+pub struct TaskGroup(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+	executor: Arc<Executor>,
+	id: usize,
+	next: AtomicUsize,
+	tasks: Mutex<HashMap<usize, AbortHandle>>,
+}
+
+/// Removes a [`Task`] from it's [`TaskGroup`] when it finishes or is
+/// cancelled.
+struct Registered {
+	group: Arc<Inner>,
+	key: usize,
+}
+
+impl Drop for Registered {
+	fn drop(&mut self) {
+		drop(self.group.tasks.lock().remove(&self.key));
+	}
+}
+
+impl TaskGroup {
+	/// Creates a [`TaskGroup`] served by all workers of the current
+	/// [`Executor`].
+	#[must_use]
+	pub fn new() -> Self {
+		Self::create(None)
+	}
+
+	/// Creates a [`TaskGroup`] only served by the given workers of the current
+	/// [`Executor`], `0` being the thread that started it.
+	///
+	/// # Panics
+	/// Panics if `workers` is empty or contains an index with no worker.
+	#[must_use]
+	pub fn with_workers<W: IntoIterator<Item = usize>>(workers: W) -> Self {
+		Self::create(Some(workers.into_iter().collect()))
+	}
+
+	fn create(workers: Option<Vec<usize>>) -> Self {
+		let executor = Worker::with(|worker| Arc::clone(&worker.executor));
+
+		if let Some(workers) = &workers {
+			assert!(!workers.is_empty(), "`TaskGroup` needs at least one worker");
+			assert!(
+				workers.iter().all(|worker| *worker < executor.workers()),
+				"worker doesn't exist"
+			);
+		}
+
+		let id = executor.create_group(workers);
+
+		Self(Arc::new(Inner {
+			executor,
+			id,
+			next: AtomicUsize::new(0),
+			tasks: Mutex::default(),
+		}))
+	}
+
+	/// Spawns a [`Task`] in this [`TaskGroup`].
+	pub fn spawn<F>(&self, future: F) -> Task<F::Output>
+	where
+		F: Future + Send + 'static,
+		F::Output: Send + 'static,
+	{
+		self.spawn_with_priority(0, future)
+	}
+
+	/// Spawns a [`Task`] in this [`TaskGroup`] with the given `priority`, see
+	/// [`Task::spawn_with_priority`].
+	pub fn spawn_with_priority<F>(&self, priority: u8, future: F) -> Task<F::Output>
+	where
+		F: Future + Send + 'static,
+		F::Output: Send + 'static,
+	{
+		let (handle, registration) = AbortHandle::new_pair();
+		let key = self.0.next.fetch_add(1, Ordering::Relaxed);
+		drop(self.0.tasks.lock().insert(key, handle));
+
+		let registered = Registered {
+			group: Arc::clone(&self.0),
+			key,
+		};
+
+		Task::spawn_with(
+			Arc::clone(&self.0.executor),
+			priority,
+			self.0.id,
+			None,
+			Abortable::new(future, registration),
+			move |result| {
+				drop(registered);
+				result.map_err(|_aborted| error::Join::Cancelled)
+			},
+		)
+	}
+
+	/// Cancels all [`Task`]s currently running in this [`TaskGroup`].
+	pub fn cancel(&self) {
+		for (_, handle) in self.0.tasks.lock().drain() {
+			handle.abort();
+		}
+	}
+
+	/// Returns the number of [`Task`]s currently running in this
+	/// [`TaskGroup`].
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.0.tasks.lock().len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// Tasks keep their [`TaskGroup`] alive, so it's only removed once all of
+/// them finished.
+impl Drop for Inner {
+	fn drop(&mut self) {
+		self.executor.remove_group(self.id);
+	}
+}
+
+impl Default for TaskGroup {
+	fn default() -> Self {
+		Self::new()
+	}
+}
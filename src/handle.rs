@@ -0,0 +1,91 @@
+use std::{
+	future::Future,
+	sync::{atomic::Ordering, Arc},
+};
+
+use crate::{executor::TaskCounts, metrics::Metrics, Executor, Task, Worker};
+
+/// Handle to an [`Executor`], allowing to spawn [`Task`]s from any thread.
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Executor>);
+
+impl Handle {
+	pub(crate) fn new(executor: Arc<Executor>) -> Self {
+		Self(executor)
+	}
+
+	/// Returns a [`Handle`] to the [`Executor`] running on the current thread.
+	///
+	/// # Panics
+	/// Panics if the current thread isn't a worker of an [`Executor`].
+	#[must_use]
+	pub fn current() -> Self {
+		Self::try_current().expect("`Worker` not initialized")
+	}
+
+	/// Returns a [`Handle`] to the [`Executor`] running on the current thread,
+	/// if there is any.
+	#[must_use]
+	pub fn try_current() -> Option<Self> {
+		Worker::try_with(|worker| worker.map(|worker| Self(Arc::clone(&worker.executor))))
+	}
+
+	/// Returns a snapshot of the runtime statistics of the [`Executor`], see
+	/// [`Executor::metrics`].
+	#[must_use]
+	pub fn metrics(&self) -> Metrics {
+		self.0.snapshot()
+	}
+
+	/// Returns the number of tasks that didn't finish yet, see
+	/// [`Executor::active_tasks`].
+	#[must_use]
+	pub fn active_tasks(&self) -> usize {
+		self.0.tasks.load(Ordering::SeqCst)
+	}
+
+	/// Returns a stream of task counts, see [`Executor::task_counts`].
+	#[must_use]
+	pub fn task_counts(&self) -> TaskCounts {
+		self.0.subscribe_counts()
+	}
+
+	/// Spawns `future` on the [`Executor`].
+	pub fn spawn<F>(&self, future: F) -> Task<F::Output>
+	where
+		F: Future + Send + 'static,
+		F::Output: Send + 'static,
+	{
+		Task::spawn_with(Arc::clone(&self.0), 0, 0, None, future, Ok)
+	}
+
+	/// Spawns `future` on the [`Executor`] with the given `priority`, see
+	/// [`Task::spawn_with_priority`].
+	pub fn spawn_with_priority<F>(&self, priority: u8, future: F) -> Task<F::Output>
+	where
+		F: Future + Send + 'static,
+		F::Output: Send + 'static,
+	{
+		Task::spawn_with(Arc::clone(&self.0), priority, 0, None, future, Ok)
+	}
+
+	/// Runs `function` on the blocking thread pool of the [`Executor`], see
+	/// [`spawn_blocking`](crate::spawn_blocking).
+	pub fn spawn_blocking<F, R>(&self, function: F) -> Task<R>
+	where
+		F: FnOnce() -> R + Send + 'static,
+		R: Send + 'static,
+	{
+		Task::spawn_blocking_with(Arc::clone(&self.0), function)
+	}
+
+	/// Runs `future` on the [`Executor`], blocking the current thread until
+	/// it's finished.
+	pub fn block_on<F>(&self, future: F) -> F::Output
+	where
+		F: Future + Send,
+		F::Output: Send,
+	{
+		Task::block_on_with(Arc::clone(&self.0), future)
+	}
+}
@@ -59,12 +59,28 @@
 )]
 #![cfg_attr(doc, allow(rustdoc::all))]
 
-mod error;
+mod blocking;
+mod builder;
+pub mod coop;
+pub mod error;
 mod executor;
-mod task;
+mod group;
+mod handle;
+pub mod metrics;
+pub mod net;
+mod reactor;
+mod set;
+pub mod task;
+pub mod time;
+mod topology;
 mod worker;
 
 pub use allochronic_macros::executor;
-pub use executor::Executor;
-pub use task::Task;
-use worker::{Message, Runnables, Worker};
+pub use blocking::spawn_blocking;
+pub use builder::{Affinity, Builder, Idle, StealPolicy};
+pub use executor::{Executor, TaskCounts};
+pub use group::TaskGroup;
+pub use handle::Handle;
+pub use set::TaskSet;
+pub use task::{scope, Task, TaskId};
+use worker::{Message, Worker};
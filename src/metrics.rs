@@ -0,0 +1,108 @@
+//! Runtime statistics of an [`Executor`](crate::Executor), see
+//! [`Executor::metrics`](crate::Executor::metrics).
+
+use std::{
+	convert::TryFrom,
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+
+use crate::executor::Channels;
+
+/// Snapshot of the runtime statistics of an [`Executor`](crate::Executor).
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+	/// Total number of tasks spawned, including futures blocked on.
+	pub spawned: u64,
+	/// Number of tasks that didn't finish yet.
+	pub alive: usize,
+	/// Number of tasks waiting in the global injectors.
+	pub injected: usize,
+	/// Statistics of each worker, `0` being the thread that started the
+	/// [`Executor`](crate::Executor).
+	pub workers: Vec<WorkerMetrics>,
+}
+
+/// Snapshot of the runtime statistics of a single worker.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WorkerMetrics {
+	/// Total number of tasks polled.
+	pub polled: u64,
+	/// Tasks taken from the worker's local queue.
+	pub local: u64,
+	/// Tasks taken from the worker's group queues.
+	pub group: u64,
+	/// Tasks taken from the global injectors.
+	pub injector: u64,
+	/// Tasks stolen from other workers.
+	pub stolen: u64,
+	/// Total time spent parked, waiting for tasks, I/O events or timers.
+	pub parked: Duration,
+	/// Number of tasks waiting in the worker's group queues.
+	pub queued: usize,
+}
+
+/// Counters of a single worker, updated by the worker itself.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+	local: AtomicU64,
+	group: AtomicU64,
+	injector: AtomicU64,
+	stolen: AtomicU64,
+	/// Nanoseconds.
+	parked: AtomicU64,
+}
+
+/// Queue a task was taken from.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Source {
+	Local,
+	Group,
+	Injector,
+	Stolen,
+}
+
+impl Counters {
+	pub(crate) fn polled(&self, source: Source) {
+		let counter = match source {
+			Source::Local => &self.local,
+			Source::Group => &self.group,
+			Source::Injector => &self.injector,
+			Source::Stolen => &self.stolen,
+		};
+
+		counter.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn parked(&self, duration: Duration) {
+		self.parked.fetch_add(
+			u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+			Ordering::Relaxed,
+		);
+	}
+
+	/// Takes a snapshot of the counters of worker `index`, counting the tasks
+	/// waiting in it's queues of `channels`.
+	pub(crate) fn snapshot<'c, C>(&self, index: usize, channels: C) -> WorkerMetrics
+	where
+		C: Iterator<Item = &'c Channels>,
+	{
+		let local = self.local.load(Ordering::Relaxed);
+		let group = self.group.load(Ordering::Relaxed);
+		let injector = self.injector.load(Ordering::Relaxed);
+		let stolen = self.stolen.load(Ordering::Relaxed);
+
+		WorkerMetrics {
+			polled: local + group + injector + stolen,
+			local,
+			group,
+			injector,
+			stolen,
+			parked: Duration::from_nanos(self.parked.load(Ordering::Relaxed)),
+			queued: channels
+				.filter_map(|channels| channels.workers.get(index))
+				.map(|(_, stealer)| stealer.len())
+				.sum(),
+		}
+	}
+}
@@ -0,0 +1,100 @@
+//! Networking types driven by the reactor of an [`Executor`].
+
+mod tcp;
+mod udp;
+#[cfg(unix)]
+mod unix;
+
+use std::{
+	io,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+use futures_util::future;
+use mio::event;
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
+#[cfg(unix)]
+pub use unix::UnixStream;
+
+use crate::{
+	reactor::{Direction, Source},
+	Executor, Worker,
+};
+
+/// I/O object registered in the reactor of an [`Executor`], deregistered when
+/// dropped.
+#[derive(Debug)]
+struct Registered<T: event::Source> {
+	io: T,
+	source: Arc<Source>,
+	executor: Arc<Executor>,
+}
+
+impl<T: event::Source> Drop for Registered<T> {
+	fn drop(&mut self) {
+		self.executor.reactor.deregister(&mut self.io, &self.source);
+	}
+}
+
+impl<T: event::Source> Registered<T> {
+	/// Registers `io` in the reactor of the current [`Executor`].
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	fn new(mut io: T) -> io::Result<Self> {
+		let executor = Worker::try_with(|worker| worker.map(|worker| Arc::clone(&worker.executor)))
+			.expect("I/O objects can only be created inside of an `Executor`");
+		let source = executor.reactor.register(&mut io)?;
+
+		Ok(Self {
+			io,
+			source,
+			executor,
+		})
+	}
+
+	fn poll_read_with<R, F>(&self, cx: &mut Context<'_>, mut io: F) -> Poll<io::Result<R>>
+	where
+		F: FnMut(&T) -> io::Result<R>,
+	{
+		self.source.poll_io(Direction::Read, cx, || io(&self.io))
+	}
+
+	fn poll_write_with<R, F>(&self, cx: &mut Context<'_>, mut io: F) -> Poll<io::Result<R>>
+	where
+		F: FnMut(&T) -> io::Result<R>,
+	{
+		self.source.poll_io(Direction::Write, cx, || io(&self.io))
+	}
+
+	async fn read_with<R, F>(&self, mut io: F) -> io::Result<R>
+	where
+		F: FnMut(&T) -> io::Result<R>,
+	{
+		future::poll_fn(|cx| self.poll_read_with(cx, &mut io)).await
+	}
+
+	async fn write_with<R, F>(&self, mut io: F) -> io::Result<R>
+	where
+		F: FnMut(&T) -> io::Result<R>,
+	{
+		future::poll_fn(|cx| self.poll_write_with(cx, &mut io)).await
+	}
+}
+
+/// Checks if a non-blocking connect finished, returns
+/// [`io::ErrorKind::WouldBlock`] if it's still in progress.
+fn connected<A>(error: io::Result<Option<io::Error>>, peer_addr: io::Result<A>) -> io::Result<()> {
+	if let Some(error) = error? {
+		return Err(error);
+	}
+
+	match peer_addr {
+		Ok(_) => Ok(()),
+		Err(error) if error.kind() == io::ErrorKind::NotConnected =>
+			Err(io::ErrorKind::WouldBlock.into()),
+		Err(error) => Err(error),
+	}
+}
@@ -0,0 +1,124 @@
+use std::{
+	io::{self, Read, Write},
+	net::{self, Shutdown, SocketAddr},
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use super::Registered;
+
+/// TCP socket listening for connections.
+#[derive(Debug)]
+pub struct TcpListener(Registered<mio::net::TcpListener>);
+
+impl TcpListener {
+	/// Binds to `addr`.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+		Registered::new(mio::net::TcpListener::bind(addr)?).map(Self)
+	}
+
+	/// Converts a [`std::net::TcpListener`], switching it to non-blocking mode.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn from_std(listener: net::TcpListener) -> io::Result<Self> {
+		listener.set_nonblocking(true)?;
+		Registered::new(mio::net::TcpListener::from_std(listener)).map(Self)
+	}
+
+	/// Waits for a new connection.
+	pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+		let (stream, addr) = self.0.read_with(mio::net::TcpListener::accept).await?;
+
+		Ok((TcpStream(Registered::new(stream)?), addr))
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.local_addr()
+	}
+}
+
+/// TCP connection.
+#[derive(Debug)]
+pub struct TcpStream(Registered<mio::net::TcpStream>);
+
+impl AsyncRead for TcpStream {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.0.poll_read_with(cx, |mut io| io.read(buf))
+	}
+}
+
+impl AsyncWrite for TcpStream {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		self.0.poll_write_with(cx, |mut io| io.write(buf))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(self.0.io.shutdown(Shutdown::Write))
+	}
+}
+
+impl TcpStream {
+	/// Connects to `addr`.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+		let stream = Self(Registered::new(mio::net::TcpStream::connect(addr)?)?);
+
+		// connecting finished once the socket is writable
+		stream
+			.0
+			.write_with(|io| super::connected(io.take_error(), io.peer_addr()))
+			.await?;
+
+		Ok(stream)
+	}
+
+	/// Converts a connected [`std::net::TcpStream`], switching it to
+	/// non-blocking mode.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn from_std(stream: net::TcpStream) -> io::Result<Self> {
+		stream.set_nonblocking(true)?;
+		Registered::new(mio::net::TcpStream::from_std(stream)).map(Self)
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.local_addr()
+	}
+
+	pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.peer_addr()
+	}
+
+	pub fn nodelay(&self) -> io::Result<bool> {
+		self.0.io.nodelay()
+	}
+
+	pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+		self.0.io.set_nodelay(nodelay)
+	}
+
+	pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+		self.0.io.shutdown(how)
+	}
+}
@@ -0,0 +1,61 @@
+use std::{
+	io,
+	net::{self, SocketAddr},
+};
+
+use super::Registered;
+
+/// UDP socket.
+#[derive(Debug)]
+pub struct UdpSocket(Registered<mio::net::UdpSocket>);
+
+impl UdpSocket {
+	/// Binds to `addr`.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+		Registered::new(mio::net::UdpSocket::bind(addr)?).map(Self)
+	}
+
+	/// Converts a [`std::net::UdpSocket`], switching it to non-blocking mode.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn from_std(socket: net::UdpSocket) -> io::Result<Self> {
+		socket.set_nonblocking(true)?;
+		Registered::new(mio::net::UdpSocket::from_std(socket)).map(Self)
+	}
+
+	/// Sets the default address for [`send`](Self::send) and only receives
+	/// from it.
+	pub fn connect(&self, addr: SocketAddr) -> io::Result<()> {
+		self.0.io.connect(addr)
+	}
+
+	pub async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+		self.0.write_with(|io| io.send_to(buf, target)).await
+	}
+
+	pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+		self.0.read_with(|io| io.recv_from(buf)).await
+	}
+
+	/// Sends to the address set by [`connect`](Self::connect).
+	pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+		self.0.write_with(|io| io.send(buf)).await
+	}
+
+	/// Receives from the address set by [`connect`](Self::connect).
+	pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read_with(|io| io.recv(buf)).await
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.local_addr()
+	}
+
+	pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.peer_addr()
+	}
+}
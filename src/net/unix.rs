@@ -0,0 +1,97 @@
+use std::{
+	io::{self, Read, Write},
+	net::Shutdown,
+	os::unix::net::{self, SocketAddr},
+	path::Path,
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use super::Registered;
+
+/// Unix domain socket connection.
+#[derive(Debug)]
+pub struct UnixStream(Registered<mio::net::UnixStream>);
+
+impl AsyncRead for UnixStream {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.0.poll_read_with(cx, |mut io| io.read(buf))
+	}
+}
+
+impl AsyncWrite for UnixStream {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		self.0.poll_write_with(cx, |mut io| io.write(buf))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(self.0.io.shutdown(Shutdown::Write))
+	}
+}
+
+impl UnixStream {
+	/// Connects to the socket at `path`.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub async fn connect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let stream = Self(Registered::new(mio::net::UnixStream::connect(path)?)?);
+
+		// connecting finished once the socket is writable
+		stream
+			.0
+			.write_with(|io| super::connected(io.take_error(), io.peer_addr()))
+			.await?;
+
+		Ok(stream)
+	}
+
+	/// Creates a pair of connected sockets.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn pair() -> io::Result<(Self, Self)> {
+		let (first, second) = mio::net::UnixStream::pair()?;
+
+		Ok((
+			Self(Registered::new(first)?),
+			Self(Registered::new(second)?),
+		))
+	}
+
+	/// Converts a connected [`std::os::unix::net::UnixStream`], switching it
+	/// to non-blocking mode.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn from_std(stream: net::UnixStream) -> io::Result<Self> {
+		stream.set_nonblocking(true)?;
+		Registered::new(mio::net::UnixStream::from_std(stream)).map(Self)
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.local_addr()
+	}
+
+	pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+		self.0.io.peer_addr()
+	}
+
+	pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+		self.0.io.shutdown(how)
+	}
+}
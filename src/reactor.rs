@@ -0,0 +1,201 @@
+use std::{
+	io, mem,
+	sync::Arc,
+	task::{Context, Poll, Waker},
+	time::Duration,
+};
+
+use mio::{event, Events, Interest, Registry, Token};
+use parking_lot::{Mutex, MutexGuard};
+use slab::Slab;
+
+/// Token of the [`mio::Waker`] interrupting [`Polling::poll`].
+const WAKER: Token = Token(usize::MAX);
+
+/// Waits for I/O events, polled by one parked worker at a time.
+#[derive(Debug)]
+pub(crate) struct Reactor {
+	poll: Mutex<(mio::Poll, Events)>,
+	registry: Registry,
+	waker: Arc<mio::Waker>,
+	/// Registered sources by token, tokens of deregistered ones are reused.
+	sources: Mutex<Slab<Arc<Source>>>,
+}
+
+/// I/O source registered in the [`Reactor`].
+#[derive(Debug)]
+pub(crate) struct Source {
+	token: Token,
+	readable: Mutex<Readiness>,
+	writable: Mutex<Readiness>,
+}
+
+#[derive(Debug, Default)]
+struct Readiness {
+	/// An event arrived that wasn't consumed yet.
+	ready: bool,
+	/// Tasks waiting for the next event, a shared source can have many.
+	wakers: Vec<Waker>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Direction {
+	Read,
+	Write,
+}
+
+impl Reactor {
+	pub(crate) fn new() -> io::Result<Self> {
+		let poll = mio::Poll::new()?;
+		let registry = poll.registry().try_clone()?;
+		let waker = Arc::new(mio::Waker::new(poll.registry(), WAKER)?);
+
+		Ok(Self {
+			poll: Mutex::new((poll, Events::with_capacity(1024))),
+			registry,
+			waker,
+			sources: Mutex::default(),
+		})
+	}
+
+	/// Returns the waker interrupting [`Polling::poll`].
+	pub(crate) fn waker(&self) -> Arc<mio::Waker> {
+		Arc::clone(&self.waker)
+	}
+
+	/// Locks the [`Reactor`] for polling, returns [`None`] if another worker
+	/// is already polling it.
+	pub(crate) fn try_lock(&self) -> Option<Polling<'_>> {
+		self.poll.try_lock().map(|poll| Polling {
+			reactor: self,
+			poll,
+		})
+	}
+
+	pub(crate) fn register<S: event::Source>(&self, io: &mut S) -> io::Result<Arc<Source>> {
+		let source = {
+			let mut sources = self.sources.lock();
+			let entry = sources.vacant_entry();
+			let source = Arc::new(Source {
+				token: Token(entry.key()),
+				readable: Mutex::default(),
+				writable: Mutex::default(),
+			});
+			entry.insert(Arc::clone(&source));
+
+			source
+		};
+		let token = source.token;
+
+		if let Err(error) =
+			self.registry
+				.register(io, token, Interest::READABLE | Interest::WRITABLE)
+		{
+			drop(self.sources.lock().remove(token.0));
+			return Err(error);
+		}
+
+		Ok(source)
+	}
+
+	pub(crate) fn deregister<S: event::Source>(&self, io: &mut S, source: &Source) {
+		// the source might have been closed already
+		let _result = self.registry.deregister(io);
+		// stale events of a reused token only cause a spurious wake up
+		drop(self.sources.lock().try_remove(source.token.0));
+	}
+}
+
+/// Exclusive access to poll the [`Reactor`].
+#[derive(Debug)]
+pub(crate) struct Polling<'r> {
+	reactor: &'r Reactor,
+	poll: MutexGuard<'r, (mio::Poll, Events)>,
+}
+
+impl Polling<'_> {
+	/// Waits up to `timeout` for I/O events, waking up the tasks waiting for
+	/// them.
+	pub(crate) fn poll(&mut self, timeout: Option<Duration>) {
+		let (poll, events) = &mut *self.poll;
+
+		match poll.poll(events, timeout) {
+			Ok(()) => (),
+			Err(error) if error.kind() == io::ErrorKind::Interrupted => return,
+			Err(error) => panic!("failed to poll reactor: {}", error),
+		}
+
+		let mut wakers = Vec::new();
+
+		{
+			let sources = self.reactor.sources.lock();
+
+			for event in &*events {
+				if let Some(source) = sources.get(event.token().0) {
+					if event.is_readable() || event.is_read_closed() || event.is_error() {
+						wakers.extend(source.readable.lock().ready());
+					}
+
+					if event.is_writable() || event.is_write_closed() || event.is_error() {
+						wakers.extend(source.writable.lock().ready());
+					}
+				}
+			}
+		}
+
+		for waker in wakers {
+			waker.wake();
+		}
+	}
+}
+
+impl Readiness {
+	fn ready(&mut self) -> Vec<Waker> {
+		self.ready = true;
+		mem::take(&mut self.wakers)
+	}
+
+	fn register(&mut self, waker: &Waker) {
+		if !self.wakers.iter().any(|other| other.will_wake(waker)) {
+			self.wakers.push(waker.clone());
+		}
+	}
+}
+
+impl Source {
+	/// Runs `io` until it doesn't return [`io::ErrorKind::WouldBlock`]
+	/// anymore, registering `cx` to be woken up by the next event of
+	/// `direction` otherwise.
+	pub(crate) fn poll_io<R, F>(
+		&self,
+		direction: Direction,
+		cx: &mut Context<'_>,
+		mut io: F,
+	) -> Poll<io::Result<R>>
+	where
+		F: FnMut() -> io::Result<R>,
+	{
+		let readiness = match direction {
+			Direction::Read => &self.readable,
+			Direction::Write => &self.writable,
+		};
+
+		loop {
+			match io() {
+				Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+					let mut readiness = readiness.lock();
+
+					// an event arrived in the meantime, try again
+					if readiness.ready {
+						readiness.ready = false;
+						continue;
+					}
+
+					readiness.register(cx.waker());
+					return Poll::Pending;
+				}
+				result => return Poll::Ready(result),
+			}
+		}
+	}
+}
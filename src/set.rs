@@ -0,0 +1,79 @@
+use std::{
+	future::Future,
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use futures_util::{future, stream::FuturesUnordered, Stream, StreamExt};
+
+use crate::{error, Task};
+
+/// Collection of [`Task`]s, yielding their outputs in the order they finish.
+///
+/// Dropping the [`TaskSet`] cancels all [`Task`]s still in it.
+#[derive(Debug)]
+pub struct TaskSet<R>(FuturesUnordered<Task<R>>);
+
+impl<R> Stream for TaskSet<R> {
+	type Item = Result<R, error::Join>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.0.poll_next_unpin(cx)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.len(), Some(self.len()))
+	}
+}
+
+impl<R> TaskSet<R> {
+	#[must_use]
+	pub fn new() -> Self {
+		Self(FuturesUnordered::new())
+	}
+
+	/// Spawns `future` on the current [`Executor`](crate::Executor) and adds
+	/// it to this [`TaskSet`].
+	pub fn spawn<F>(&mut self, future: F)
+	where
+		F: Future<Output = R> + Send + 'static,
+		R: Send + 'static,
+	{
+		self.push(Task::spawn(future));
+	}
+
+	/// Adds an already spawned [`Task`] to this [`TaskSet`], which cancels it
+	/// when dropped.
+	pub fn push(&mut self, task: Task<R>) {
+		self.0.push(task.cancel_on_drop());
+	}
+
+	/// Waits for the next [`Task`] to finish, returns [`None`] if the
+	/// [`TaskSet`] is empty.
+	pub async fn join_next(&mut self) -> Option<Result<R, error::Join>> {
+		future::poll_fn(|cx| self.0.poll_next_unpin(cx)).await
+	}
+
+	/// Cancels all [`Task`]s without waiting for them to stop running,
+	/// leaving the [`TaskSet`] empty.
+	pub fn abort_all(&mut self) {
+		self.0.clear();
+	}
+
+	/// Returns the number of [`Task`]s that didn't finish yet.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl<R> Default for TaskSet<R> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,220 @@
+use std::{
+	any::Any,
+	cell::RefCell,
+	fmt::{self, Debug, Formatter},
+	future::Future,
+	mem,
+	pin::Pin,
+	task::{Context, Poll},
+	thread,
+};
+
+use crate::error;
+
+thread_local!(static ENTERED: RefCell<Vec<&'static dyn Slot>> = RefCell::new(Vec::new()));
+
+/// Declares [`LocalKey`]s, values that follow a [`Task`](crate::Task)
+/// across workers.
+///
+/// ```
+/// allochronic::task_local! {
+/// 	static REQUEST: u64;
+/// }
+/// ```
+#[macro_export]
+macro_rules! task_local {
+	() => {};
+	($(#[$attr:meta])* $vis:vis static $name:ident: $type:ty; $($rest:tt)*) => {
+		$crate::task_local!($(#[$attr])* $vis static $name: $type);
+		$crate::task_local!($($rest)*);
+	};
+	($(#[$attr:meta])* $vis:vis static $name:ident: $type:ty) => {
+		$(#[$attr])*
+		$vis static $name: $crate::task::LocalKey<$type> = {
+			::std::thread_local!(
+				static KEY: ::std::cell::RefCell<::std::option::Option<$type>> =
+					::std::cell::RefCell::new(::std::option::Option::None)
+			);
+
+			$crate::task::LocalKey::new(KEY)
+		};
+	};
+}
+
+/// Key of a task-local value, declared by [`task_local!`](crate::task_local).
+///
+/// Values are stored in the future passed to [`scope`](Self::scope) and only
+/// moved into a thread-local while it's polled, so they stay with the
+/// [`Task`](crate::Task) even if it's stolen by another worker. They are moved
+/// out again while [`Task::block_on`](crate::Task::block_on) runs other tasks,
+/// including the future it blocks on.
+pub struct LocalKey<T: 'static>(thread::LocalKey<RefCell<Option<T>>>);
+
+/// Future returned by [`LocalKey::scope`].
+pub struct TaskLocalFuture<T: 'static, F> {
+	key: &'static LocalKey<T>,
+	value: Option<T>,
+	future: Pin<Box<F>>,
+}
+
+/// Moves the value back into the [`TaskLocalFuture`] when dropped, even when
+/// unwinding.
+struct Reset<'v, T: 'static> {
+	key: &'static LocalKey<T>,
+	value: &'v mut Option<T>,
+}
+
+/// Moves the values taken by [`suspend`] back into their thread-locals when
+/// dropped, even when unwinding.
+struct Resume(Vec<(&'static dyn Slot, Option<Box<dyn Any>>)>);
+
+/// Type-erased access to the thread-local of a [`LocalKey`].
+trait Slot {
+	fn take(&'static self) -> Option<Box<dyn Any>>;
+
+	fn restore(&'static self, value: Option<Box<dyn Any>>);
+}
+
+impl<T: 'static> Debug for LocalKey<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("LocalKey").finish_non_exhaustive()
+	}
+}
+
+impl<T: 'static, F> Debug for TaskLocalFuture<T, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TaskLocalFuture").finish_non_exhaustive()
+	}
+}
+
+// the value is never pinned, it's moved in and out of the thread-local
+impl<T: 'static, F> Unpin for TaskLocalFuture<T, F> {}
+
+impl<T: 'static> Drop for Reset<'_, T> {
+	fn drop(&mut self) {
+		ENTERED.with(|entered| entered.borrow_mut().pop());
+		self.key.swap(self.value);
+	}
+}
+
+impl Drop for Resume {
+	fn drop(&mut self) {
+		// restore in reverse, the first entry of a key nested in itself holds it's
+		// value
+		for (key, value) in self.0.iter_mut().rev() {
+			key.restore(value.take());
+		}
+
+		ENTERED.with(|entered| {
+			*entered.borrow_mut() = self.0.iter().map(|(key, _)| *key).collect();
+		});
+	}
+}
+
+impl<T: 'static> Slot for LocalKey<T> {
+	fn take(&'static self) -> Option<Box<dyn Any>> {
+		self.0.with(|current| {
+			let value = current
+				.try_borrow_mut()
+				.expect("task-local value is borrowed")
+				.take()?;
+			let value: Box<dyn Any> = Box::new(value);
+
+			Some(value)
+		})
+	}
+
+	fn restore(&'static self, value: Option<Box<dyn Any>>) {
+		let value = value.map(|value| *value.downcast().expect("wrong task-local type"));
+		self.0.with(|current| {
+			*current
+				.try_borrow_mut()
+				.expect("task-local value is borrowed") = value;
+		});
+	}
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+	type Output = F::Output;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let this = self.get_mut();
+		let future = &mut this.future;
+
+		this.key.enter(&mut this.value, || future.as_mut().poll(cx))
+	}
+}
+
+impl<T: 'static> LocalKey<T> {
+	#[doc(hidden)]
+	pub const fn new(key: thread::LocalKey<RefCell<Option<T>>>) -> Self {
+		Self(key)
+	}
+
+	/// Sets the value to `value` while `future` is polled.
+	pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+		TaskLocalFuture {
+			key: self,
+			value: Some(value),
+			future: Box::pin(future),
+		}
+	}
+
+	/// Calls `function` with a reference to the value.
+	///
+	/// # Panics
+	/// Panics if not called inside of [`scope`](Self::scope).
+	pub fn with<F: FnOnce(&T) -> R, R>(&'static self, function: F) -> R {
+		self.try_with(function)
+			.expect("task-local value accessed outside of it's scope")
+	}
+
+	/// Calls `function` with a reference to the value, fails if not called
+	/// inside of [`scope`](Self::scope).
+	pub fn try_with<F, R>(&'static self, function: F) -> Result<R, error::Access>
+	where
+		F: FnOnce(&T) -> R,
+	{
+		self.0
+			.with(|value| value.borrow().as_ref().map(function).ok_or(error::Access))
+	}
+
+	/// Moves `value` into the thread-local while calling `function`.
+	fn enter<F: FnOnce() -> R, R>(&'static self, value: &mut Option<T>, function: F) -> R {
+		self.swap(value);
+		ENTERED.with(|entered| entered.borrow_mut().push(self));
+		let _reset = Reset { key: self, value };
+
+		function()
+	}
+
+	fn swap(&'static self, value: &mut Option<T>) {
+		self.0.with(|current| {
+			mem::swap(
+				&mut *current
+					.try_borrow_mut()
+					.expect("task-local value is borrowed"),
+				value,
+			);
+		});
+	}
+}
+
+impl<T: Clone + 'static> LocalKey<T> {
+	/// Returns a copy of the value.
+	///
+	/// # Panics
+	/// Panics if not called inside of [`scope`](Self::scope).
+	pub fn get(&'static self) -> T {
+		self.with(T::clone)
+	}
+}
+
+/// Moves all task-local values out of their thread-locals while calling
+/// `function`, so tasks it runs don't see them.
+pub(crate) fn suspend<F: FnOnce() -> R, R>(function: F) -> R {
+	let keys = ENTERED.with(|entered| mem::take(&mut *entered.borrow_mut()));
+	let _resume = Resume(keys.into_iter().map(|key| (key, key.take())).collect());
+
+	function()
+}
@@ -0,0 +1,420 @@
+//! Spawning and identifying [`Task`]s.
+
+mod local;
+mod scope;
+mod unwind;
+
+use std::{
+	fmt::{self, Display, Formatter},
+	future::Future,
+	panic::AssertUnwindSafe,
+	pin::Pin,
+	process,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+use allochronic_task::{Metadata, Runnable};
+use futures_util::FutureExt;
+#[cfg(feature = "tokio-support")]
+use tokio_util::context::TokioContext;
+
+use self::unwind::{CatchDrop, Output};
+pub use self::{
+	local::{LocalKey, TaskLocalFuture},
+	scope::{scope, Scope},
+};
+use crate::{blocking::Blocking, error, Executor, Message, Worker};
+
+#[derive(Debug)]
+pub struct Task<R>(allochronic_task::Task<Output<Result<R, error::Join>>>);
+
+/// Identifies a [`Task`], unique per [`Executor`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TaskId(u64);
+
+/// Configures a [`Task`] before spawning it, see [`Task::builder`].
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct Builder {
+	name: Option<String>,
+	priority: u8,
+}
+
+/// Returns the [`TaskId`] of the [`Task`] running on the current thread, if
+/// there is any.
+#[must_use]
+pub fn current_id() -> Option<TaskId> {
+	allochronic_task::current().map(TaskId)
+}
+
+impl Display for TaskId {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		self.0.fmt(f)
+	}
+}
+
+impl Builder {
+	/// Names the [`Task`], see [`Task::name`].
+	pub fn name<N: Into<String>>(mut self, name: N) -> Self {
+		self.name = Some(name.into());
+		self
+	}
+
+	/// Sets the priority of the [`Task`], see [`Task::spawn_with_priority`].
+	/// Defaults to `0`.
+	pub const fn priority(mut self, priority: u8) -> Self {
+		self.priority = priority;
+		self
+	}
+
+	/// Spawns `future` with this configuration, see [`Task::spawn`].
+	pub fn spawn<F, R>(self, future: F) -> Task<R>
+	where
+		F: Future<Output = R> + Send + 'static,
+		R: Send + 'static,
+	{
+		Task::spawn_with(
+			Worker::with(|worker| Arc::clone(&worker.executor)),
+			self.priority,
+			0,
+			self.name,
+			future,
+			Ok,
+		)
+	}
+}
+
+impl<R> Future for Task<R> {
+	type Output = Result<R, error::Join>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		self.0.poll_unpin(cx).map(|result| match result {
+			Ok(output) => output.take(),
+			Err(allochronic_task::error::Cancelled) => Err(error::Join::Cancelled),
+		})
+	}
+}
+
+impl Task<()> {
+	/// Returns a [`Builder`] to configure a [`Task`] before spawning it.
+	pub fn builder() -> Builder {
+		Builder::default()
+	}
+}
+
+impl<R> Task<R> {
+	/// Returns the [`TaskId`] of this [`Task`].
+	#[must_use]
+	pub fn id(&self) -> TaskId {
+		TaskId(self.0.metadata().id())
+	}
+
+	/// Returns the name given by [`Builder::name`].
+	#[must_use]
+	pub fn name(&self) -> Option<&str> {
+		self.0.metadata().name()
+	}
+
+	/// Cancels the [`Task`] and waits for it to stop running. Returns the
+	/// output if it already finished, [`None`] if it was cancelled or
+	/// panicked.
+	pub async fn cancel(self) -> Option<R> {
+		self.0.cancel().await.and_then(|output| output.take().ok())
+	}
+
+	/// Cancels the [`Task`] without waiting for it to stop running.
+	pub fn abort(self) {
+		self.0.abort();
+	}
+
+	/// Lets the [`Task`] keep running in the background. This is the default
+	/// when dropping a [`Task`], see [`cancel_on_drop`](Self::cancel_on_drop).
+	pub fn detach(self) {
+		self.0.detach();
+	}
+
+	/// Makes dropping this [`Task`] cancel it instead of detaching it.
+	#[must_use]
+	pub fn cancel_on_drop(mut self) -> Self {
+		self.0.set_cancel_on_drop(true);
+		self
+	}
+
+	pub fn spawn<F>(future: F) -> Self
+	where
+		F: Future<Output = R> + Send + 'static,
+		R: Send + 'static,
+	{
+		Self::spawn_with_priority(0, future)
+	}
+
+	/// Spawns a [`Task`] with the given `priority`. Queues with lower
+	/// priorities are polled first, `0` being the highest priority and
+	/// [`u8::MAX`] the lowest. [`spawn`](Self::spawn) uses `0`.
+	pub fn spawn_with_priority<F>(priority: u8, future: F) -> Self
+	where
+		F: Future<Output = R> + Send + 'static,
+		R: Send + 'static,
+	{
+		Self::spawn_with(
+			Worker::with(|worker| Arc::clone(&worker.executor)),
+			priority,
+			0,
+			None,
+			future,
+			Ok,
+		)
+	}
+
+	/// Spawns `future` in `group`, converting it's output with `output`.
+	pub(crate) fn spawn_with<F, O>(
+		executor: Arc<Executor>,
+		priority: u8,
+		group: usize,
+		name: Option<String>,
+		future: F,
+		output: O,
+	) -> Self
+	where
+		F: Future + Send + 'static,
+		O: FnOnce(F::Output) -> Result<R, error::Join> + Send + 'static,
+		R: Send + 'static,
+	{
+		let priority = usize::from(priority);
+
+		// workers know the queues they picked up, only check the `Executor`
+		// otherwise
+		if !Worker::has_queue(&executor, priority, group) {
+			executor.register(priority, group);
+		}
+
+		let (metadata, future) = Self::wrap(&executor, name, future, output);
+		let closed = executor.is_closed();
+
+		let (runnable, task) = allochronic_task::spawn(future, metadata, {
+			let executor = Arc::clone(&executor);
+			move |runnable| Self::send_injector(&executor, priority, group, runnable, true)
+		});
+
+		// dropping the `Runnable` cancels the `Task`
+		if !closed {
+			Self::send_injector(&executor, priority, group, runnable, false);
+		}
+
+		Self(task)
+	}
+
+	/// Spawns a `!Send` [`Task`] on the current worker. It's never stolen by
+	/// other workers.
+	///
+	/// # Panics
+	/// Panics if not called from a worker.
+	pub fn spawn_local<F>(future: F) -> Self
+	where
+		F: Future<Output = R> + 'static,
+		R: 'static,
+	{
+		let (executor, sender) = Worker::with(|worker| {
+			(
+				Arc::clone(&worker.executor),
+				worker
+					.local
+					.get(0)
+					.expect("`Task::spawn_local` can't be called from a blocking thread")
+					.clone(),
+			)
+		});
+		let (metadata, future) = Self::wrap(&executor, None, future, Ok);
+
+		let (runnable, task) = allochronic_task::spawn_local(future, metadata, sender);
+
+		// dropping the `Runnable` cancels the `Task`
+		if !executor.is_closed() {
+			runnable.schedule();
+		}
+
+		Self(task)
+	}
+
+	/// Runs `function` on the blocking thread pool of `executor`.
+	pub(crate) fn spawn_blocking_with<F>(executor: Arc<Executor>, function: F) -> Self
+	where
+		F: FnOnce() -> R + Send + 'static,
+		R: Send + 'static,
+	{
+		let (metadata, future) = Self::wrap(&executor, None, async move { function() }, Ok);
+		let closed = executor.is_closed();
+
+		let (runnable, task) = allochronic_task::spawn(future, metadata, move |runnable| {
+			Blocking::schedule(&executor, runnable)
+		});
+
+		// dropping the `Runnable` cancels the `Task`
+		if !closed {
+			runnable.schedule();
+		}
+
+		Self(task)
+	}
+
+	/// Tracks `future` and catches it's panics, including when dropping it or
+	/// it's output, converting it's output with `output`. Returns the
+	/// [`Metadata`] to spawn it with.
+	fn wrap<F, O>(
+		executor: &Arc<Executor>,
+		name: Option<String>,
+		future: F,
+		output: O,
+	) -> (
+		Metadata,
+		impl Future<Output = Output<Result<R, error::Join>>>,
+	)
+	where
+		F: Future,
+		O: FnOnce(F::Output) -> Result<R, error::Join>,
+	{
+		let tracker = executor.track();
+		#[cfg(feature = "tokio-support")]
+		let future = TokioContext::new(future, executor.tokio.handle().clone());
+		#[cfg(feature = "tracing")]
+		let future = tracker.instrument(name.as_deref(), "spawned", future);
+		let future = CatchDrop::new(future, Arc::clone(executor));
+		let executor = Arc::clone(executor);
+		let metadata = Metadata::new(tracker.id(), name);
+
+		(metadata, async move {
+			let _tracker = tracker;
+
+			let result = match AssertUnwindSafe(future).catch_unwind().await {
+				Ok(result) => output(result),
+				Err(_) if executor.aborts_on_panic() => process::abort(),
+				Err(payload) => Err(error::Join::Panic(payload)),
+			};
+
+			Output::new(result, executor.aborts_on_panic())
+		})
+	}
+
+	pub fn block_on<F>(future: F) -> R
+	where
+		F: Future<Output = R> + Send,
+		R: Send,
+	{
+		// blocking threads don't run tasks, hand `future` to the async workers
+		if let Some(executor) =
+			Worker::with(|worker| worker.is_blocking().then(|| Arc::clone(&worker.executor)))
+		{
+			return Self::block_on_with(executor, future);
+		}
+
+		Worker::WORKER.with(move |worker| {
+			let worker = worker.get().expect("`Worker` not initialized");
+
+			let executor = Arc::clone(&worker.borrow().executor);
+			let tracker = executor.track();
+			#[cfg(feature = "tokio-support")]
+			let future = TokioContext::new(future, executor.tokio.handle().clone());
+			#[cfg(feature = "tracing")]
+			let future = tracker.instrument(None, "blocked on", future);
+
+			let metadata = Metadata::new(tracker.id(), None);
+
+			// task-local values of the caller mustn't leak into the tasks run meanwhile
+			local::suspend(move || {
+				allochronic_task::block_on(
+					async move {
+						let _tracker = tracker;
+						future.await
+					},
+					metadata,
+					move |runnable| Self::send_injector(&executor, 0, 0, runnable, true),
+					|runnable, mut task| {
+						runnable.schedule();
+
+						loop {
+							let message = Worker::run(&mut *worker.borrow_mut(), Some(&mut task));
+
+							match message {
+								Message::Blocked(result) => break result,
+								Message::Shutdown => break task.cancel(),
+								Message::Management(()) => worker.borrow_mut().sync(),
+								Message::Task(runnable) => {
+									runnable.run();
+								}
+							}
+						}
+					},
+				)
+				.expect("`Task` cancelled, likely because of `Executor` shutdown")
+			})
+		})
+	}
+
+	/// Blocks the current thread on `future`, which is run by `executor`. Falls
+	/// back to [`block_on`](Self::block_on) if called from one of `executor`'s
+	/// workers.
+	pub(crate) fn block_on_with<F>(executor: Arc<Executor>, future: F) -> R
+	where
+		F: Future<Output = R> + Send,
+		R: Send,
+	{
+		if Worker::is_current(&executor) {
+			return Self::block_on(future);
+		}
+
+		let tracker = executor.track();
+		#[cfg(feature = "tokio-support")]
+		let future = TokioContext::new(future, executor.tokio.handle().clone());
+		#[cfg(feature = "tracing")]
+		let future = tracker.instrument(None, "blocked on", future);
+
+		let metadata = Metadata::new(tracker.id(), None);
+
+		allochronic_task::block_on(
+			async move {
+				let _tracker = tracker;
+				future.await
+			},
+			metadata,
+			move |runnable| Self::send_injector(&executor, 0, 0, runnable, true),
+			|runnable, task| {
+				runnable.schedule();
+				futures_lite::future::block_on(task)
+			},
+		)
+		.expect("`Task` cancelled, likely because of `Executor` shutdown")
+	}
+
+	/// Queues `runnable` on the current worker or the global injector.
+	/// `woken` tasks can go into the LIFO slot, newly spawned ones are queued
+	/// to be stolen right away.
+	fn send_injector(
+		executor: &Arc<Executor>,
+		priority: usize,
+		group: usize,
+		runnable: Runnable,
+		woken: bool,
+	) {
+		// tasks woken up after shutdown are cancelled
+		if executor.is_shutdown() {
+			return;
+		}
+
+		// the worker might not have picked up newly registered queues yet
+		let stealable = Worker::try_with(|worker| match worker {
+			Some(worker) if Arc::ptr_eq(&worker.executor, executor) =>
+				worker.schedule(priority, group, runnable, woken),
+			_ => Err(runnable),
+		})
+		.unwrap_or_else(|runnable| {
+			executor.inject(priority, group, runnable);
+			true
+		});
+
+		// tasks in the LIFO slot are run by this worker
+		if stealable {
+			executor.sleepers.wake_one();
+		}
+	}
+}
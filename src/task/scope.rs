@@ -0,0 +1,214 @@
+use std::{
+	future::Future,
+	marker::PhantomData,
+	panic::{self, AssertUnwindSafe},
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+use allochronic_channel::notify::Notify;
+use futures_util::{
+	future::{AbortHandle, Abortable},
+	FutureExt,
+};
+use parking_lot::Mutex;
+
+use super::Task;
+use crate::{error, Executor, Worker};
+
+/// Runs the future returned by `body`, which can spawn [`Task`]s borrowing
+/// from the stack through [`Scope::spawn`]. Blocks until all of them
+/// finished, similar to [`std::thread::scope`].
+///
+/// Like [`Task::block_on`], the current worker keeps running other tasks
+/// while blocked.
+///
+/// # Panics
+/// Panics if not called from a worker, resumes panics of `body`'s future
+/// after all [`Task`]s finished. If `body` itself panics, all [`Task`]s are
+/// cancelled before unwinding.
+pub fn scope<'env, F, Fut>(body: F) -> Fut::Output
+where
+	F: FnOnce(Scope<'env>) -> Fut,
+	Fut: Future + Send,
+	Fut::Output: Send,
+{
+	let executor = Worker::with(|worker| Arc::clone(&worker.executor));
+	let inner = Arc::new(Inner {
+		state: Mutex::default(),
+		finished: Notify::new(),
+	});
+	let mut guard = Guard {
+		executor: Arc::clone(&executor),
+		inner: Arc::clone(&inner),
+		finished: false,
+	};
+
+	let future = body(Scope {
+		executor,
+		inner: Arc::clone(&inner),
+		env: PhantomData,
+	});
+
+	let output = Task::block_on(async move {
+		let output = AssertUnwindSafe(future).catch_unwind().await;
+		inner.wait().await;
+		output
+	});
+	guard.finished = true;
+
+	output.unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+/// Spawns [`Task`]s that can borrow anything outliving `'env`, see [`scope`].
+#[derive(Clone, Debug)]
+pub struct Scope<'env> {
+	executor: Arc<Executor>,
+	inner: Arc<Inner>,
+	/// Invariant over `'env`.
+	env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Debug)]
+struct Inner {
+	state: Mutex<State>,
+	finished: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+	running: usize,
+	/// Cancels the spawned [`Task`]s if [`scope`] unwinds.
+	aborts: Vec<AbortHandle>,
+	/// The [`scope`] returned, no further [`Task`]s can be spawned.
+	closed: bool,
+}
+
+/// Future of a scoped [`Task`], signalling the [`Scope`] after it was
+/// dropped.
+struct Child<F> {
+	// fields are dropped in order, the future has to be dropped first
+	future: Pin<Box<Abortable<F>>>,
+	_running: Running,
+}
+
+struct Running(Arc<Inner>);
+
+/// Waits for all [`Task`]s of the [`Scope`] when [`scope`] unwinds.
+struct Guard {
+	executor: Arc<Executor>,
+	inner: Arc<Inner>,
+	finished: bool,
+}
+
+impl<F: Future> Future for Child<F> {
+	type Output = <Abortable<F> as Future>::Output;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		self.future.as_mut().poll(cx)
+	}
+}
+
+impl Drop for Running {
+	fn drop(&mut self) {
+		let mut state = self.0.state.lock();
+		state.running -= 1;
+
+		if state.running == 0 {
+			self.0.finished.notify();
+		}
+	}
+}
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		if self.finished {
+			return;
+		}
+
+		for abort in self.inner.state.lock().aborts.drain(..) {
+			abort.abort();
+		}
+
+		if self.executor.is_shutdown() {
+			// after a shutdown the queues of this worker are only cleared once it
+			// stops, drop them now to not wait on tasks that never run
+			if Worker::is_current(&self.executor) {
+				Worker::with_mut(|mut worker| worker.clear());
+			}
+
+			futures_lite::future::block_on(self.inner.wait());
+		} else {
+			// cancelled tasks still have to be polled to be dropped, they might
+			// be queued on this worker
+			Task::block_on(self.inner.wait());
+		}
+	}
+}
+
+impl Inner {
+	/// Waits until all [`Task`]s finished, closing the [`Scope`].
+	async fn wait(&self) {
+		loop {
+			{
+				let mut state = self.state.lock();
+
+				if state.running == 0 {
+					state.closed = true;
+					break;
+				}
+			}
+
+			(&self.finished).await;
+		}
+	}
+}
+
+impl<'env> Scope<'env> {
+	/// Spawns `future`, which can borrow anything outliving `'env`.
+	///
+	/// # Panics
+	/// Panics if the [`scope`] already returned.
+	pub fn spawn<F>(&self, future: F) -> Task<F::Output>
+	where
+		F: Future + Send + 'env,
+		F::Output: Send + 'static,
+	{
+		let (abort, registration) = AbortHandle::new_pair();
+
+		{
+			let mut state = self.inner.state.lock();
+			assert!(!state.closed, "`Scope` already finished");
+			state.running += 1;
+			state.aborts.push(abort);
+		}
+
+		let future = Child {
+			future: Box::pin(Abortable::new(future, registration)),
+			_running: Running(Arc::clone(&self.inner)),
+		};
+
+		let (metadata, future) = Task::wrap(&self.executor, None, future, |result| {
+			result.map_err(|_aborted| error::Join::Cancelled)
+		});
+		let closed = self.executor.is_closed();
+		let executor = Arc::clone(&self.executor);
+
+		// SAFETY: `scope` doesn't return before all futures spawned here are
+		// dropped, further spawns are rejected after, the output is `'static`
+		#[allow(unsafe_code)]
+		let (runnable, task) = unsafe {
+			allochronic_task::spawn_unchecked(future, metadata, move |runnable| {
+				Task::<F::Output>::send_injector(&executor, 0, 0, runnable, true)
+			})
+		};
+
+		// dropping the `Runnable` cancels the `Task`
+		if !closed {
+			Task::<F::Output>::send_injector(&self.executor, 0, 0, runnable, false);
+		}
+
+		Task(task)
+	}
+}
@@ -0,0 +1,97 @@
+//! Catching panics when dropping the future or output of a
+//! [`Task`](crate::Task), which would otherwise unwind through the worker or
+//! abort inside of [`async_task`].
+
+use std::{
+	future::Future,
+	mem::ManuallyDrop,
+	panic::{self, AssertUnwindSafe},
+	pin::Pin,
+	process,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+use crate::Executor;
+
+/// Future of a [`Task`](crate::Task), catching panics while it's dropped.
+pub(super) struct CatchDrop<F> {
+	future: ManuallyDrop<F>,
+	executor: Arc<Executor>,
+}
+
+/// Output of a [`Task`](crate::Task), catching panics while it's dropped if
+/// it wasn't taken.
+#[derive(Debug)]
+pub(super) struct Output<R> {
+	output: Option<R>,
+	abort: bool,
+}
+
+impl<F: Future> Future for CatchDrop<F> {
+	type Output = F::Output;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		// SAFETY: `future` is never moved, only dropped in place
+		#[allow(unsafe_code)]
+		let future = unsafe { self.map_unchecked_mut(|this| &mut *this.future) };
+		future.poll(cx)
+	}
+}
+
+impl<F> Drop for CatchDrop<F> {
+	fn drop(&mut self) {
+		let future = &mut self.future;
+		let result = panic::catch_unwind(AssertUnwindSafe(|| {
+			// SAFETY: `future` isn't used after being dropped
+			#[allow(unsafe_code)]
+			unsafe {
+				ManuallyDrop::drop(future);
+			}
+		}));
+
+		caught(result, self.executor.aborts_on_panic());
+	}
+}
+
+impl<R> Drop for Output<R> {
+	fn drop(&mut self) {
+		if let Some(output) = self.output.take() {
+			caught(
+				panic::catch_unwind(AssertUnwindSafe(|| drop(output))),
+				self.abort,
+			);
+		}
+	}
+}
+
+impl<F> CatchDrop<F> {
+	pub(super) fn new(future: F, executor: Arc<Executor>) -> Self {
+		Self {
+			future: ManuallyDrop::new(future),
+			executor,
+		}
+	}
+}
+
+impl<R> Output<R> {
+	pub(super) const fn new(output: R, abort: bool) -> Self {
+		Self {
+			output: Some(output),
+			abort,
+		}
+	}
+
+	/// Takes the output, it's not dropped by the [`Output`] anymore.
+	pub(super) fn take(mut self) -> R {
+		self.output.take().expect("output already taken")
+	}
+}
+
+/// Aborts if a panic was caught and `abort` is set, otherwise it's discarded,
+/// no one is left to receive it.
+fn caught(result: std::thread::Result<()>, abort: bool) {
+	if result.is_err() && abort {
+		process::abort();
+	}
+}
@@ -0,0 +1,275 @@
+//! Timers driven by the workers of an [`Executor`](crate::Executor).
+
+use std::{
+	cmp::{Ordering, Reverse},
+	collections::BinaryHeap,
+	future::Future,
+	pin::Pin,
+	sync::{Arc, Weak},
+	task::{Context, Poll, Waker},
+	time::{Duration, Instant},
+};
+
+use futures_util::{future, Stream};
+use parking_lot::Mutex;
+
+use crate::{error, Worker};
+
+/// Deadline used for durations too large to represent, about 30 years.
+const FAR_FUTURE: Duration = Duration::from_secs(60 * 60 * 24 * 365 * 30);
+
+/// Waits until `duration` has elapsed. Durations too large to represent
+/// never elapse.
+#[must_use]
+pub fn sleep(duration: Duration) -> Sleep {
+	sleep_until(after(Instant::now(), duration))
+}
+
+/// Returns `duration` after `instant`, or [`FAR_FUTURE`] if that overflows.
+fn after(instant: Instant, duration: Duration) -> Instant {
+	instant
+		.checked_add(duration)
+		.unwrap_or_else(|| instant + FAR_FUTURE)
+}
+
+/// Waits until `deadline` is reached.
+#[must_use]
+pub const fn sleep_until(deadline: Instant) -> Sleep {
+	Sleep {
+		deadline,
+		waker: None,
+	}
+}
+
+/// Waits for `future` to finish, failing with [`error::Elapsed`] if it
+/// doesn't within `duration`.
+pub async fn timeout<F: Future>(
+	duration: Duration,
+	future: F,
+) -> Result<F::Output, error::Elapsed> {
+	let mut future = Box::pin(future);
+	let mut sleep = sleep(duration);
+
+	allochronic_util::select!(
+		output: &mut future => Ok(output),
+		_: &mut sleep => Err(error::Elapsed),
+	)
+}
+
+/// Yields every `period`, the first tick completes immediately.
+///
+/// # Panics
+/// Panics if `period` is zero.
+#[must_use]
+pub fn interval(period: Duration) -> Interval {
+	assert_ne!(period, Duration::ZERO, "`period` can't be zero");
+
+	Interval {
+		period,
+		sleep: sleep_until(Instant::now()),
+	}
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+#[derive(Debug)]
+pub struct Sleep {
+	deadline: Instant,
+	/// Waker shared with the timers of the [`Executor`](crate::Executor),
+	/// registered on the first poll.
+	waker: Option<Arc<Mutex<Option<Waker>>>>,
+}
+
+impl Future for Sleep {
+	type Output = ();
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		if Instant::now() >= self.deadline {
+			return Poll::Ready(());
+		}
+
+		if let Some(waker) = &self.waker {
+			let mut waker = waker.lock();
+
+			if !waker
+				.as_ref()
+				.map_or(false, |waker| waker.will_wake(cx.waker()))
+			{
+				*waker = Some(cx.waker().clone());
+			}
+		} else {
+			let executor =
+				Worker::try_with(|worker| worker.map(|worker| Arc::clone(&worker.executor)))
+					.expect("`Sleep` polled outside of an `Executor`");
+			let waker = Arc::new(Mutex::new(Some(cx.waker().clone())));
+
+			// workers only pick up earlier timers after they woke up
+			if executor
+				.timers
+				.insert(self.deadline, Arc::downgrade(&waker))
+				&& !Worker::is_current(&executor)
+			{
+				executor.wake();
+			}
+
+			self.waker = Some(waker);
+		}
+
+		Poll::Pending
+	}
+}
+
+impl Sleep {
+	/// Returns the [`Instant`] this [`Sleep`] finishes at.
+	#[must_use]
+	pub const fn deadline(&self) -> Instant {
+		self.deadline
+	}
+
+	/// Checks if the deadline was reached.
+	#[must_use]
+	pub fn is_elapsed(&self) -> bool {
+		Instant::now() >= self.deadline
+	}
+}
+
+/// Stream returned by [`interval`].
+///
+/// Ticks that were missed, because the [`Interval`] wasn't polled in time,
+/// are skipped.
+#[derive(Debug)]
+pub struct Interval {
+	period: Duration,
+	sleep: Sleep,
+}
+
+impl Stream for Interval {
+	type Item = Instant;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.poll_tick(cx).map(Some)
+	}
+}
+
+impl Interval {
+	/// Waits for the next tick, returning the [`Instant`] it was scheduled
+	/// for.
+	pub async fn tick(&mut self) -> Instant {
+		future::poll_fn(|cx| self.poll_tick(cx)).await
+	}
+
+	/// Polls for the next tick, see [`tick`](Self::tick).
+	pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+		if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+			return Poll::Pending;
+		}
+
+		let tick = self.sleep.deadline();
+		let now = Instant::now();
+		let mut next = after(tick, self.period);
+
+		if next <= now {
+			next = after(now, self.period);
+		}
+
+		self.sleep = sleep_until(next);
+
+		Poll::Ready(tick)
+	}
+
+	/// Returns the time between ticks.
+	#[must_use]
+	pub const fn period(&self) -> Duration {
+		self.period
+	}
+}
+
+/// Pending timers of an [`Executor`](crate::Executor), fired by it's workers.
+#[derive(Debug, Default)]
+pub(crate) struct Timers(Mutex<Heap>);
+
+#[derive(Debug, Default)]
+struct Heap {
+	timers: BinaryHeap<Reverse<Timer>>,
+	/// Number of timers after the last time dropped ones were removed.
+	pruned: usize,
+}
+
+#[derive(Debug)]
+struct Timer {
+	deadline: Instant,
+	/// Dropped if the [`Sleep`] was.
+	waker: Weak<Mutex<Option<Waker>>>,
+}
+
+impl PartialEq for Timer {
+	fn eq(&self, other: &Self) -> bool {
+		self.deadline == other.deadline
+	}
+}
+
+impl Eq for Timer {}
+
+impl PartialOrd for Timer {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Timer {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.deadline.cmp(&other.deadline)
+	}
+}
+
+impl Timers {
+	/// Adds a timer, returns `true` if it's now the earliest one.
+	///
+	/// Timers of dropped [`Sleep`]s are removed whenever the number of timers
+	/// doubled, instead of waiting for their deadline.
+	fn insert(&self, deadline: Instant, waker: Weak<Mutex<Option<Waker>>>) -> bool {
+		let mut heap = self.0.lock();
+		let Heap { timers, pruned } = &mut *heap;
+
+		if timers.len() >= (*pruned * 2).max(64) {
+			timers.retain(|Reverse(timer)| timer.waker.strong_count() > 0);
+			*pruned = timers.len();
+		}
+
+		let earliest = timers
+			.peek()
+			.map_or(true, |Reverse(timer)| deadline < timer.deadline);
+		timers.push(Reverse(Timer { deadline, waker }));
+
+		earliest
+	}
+
+	/// Wakes up all timers that are due, returning the deadline of the next
+	/// one.
+	pub(crate) fn fire(&self) -> Option<Instant> {
+		let now = Instant::now();
+		let mut wakers = Vec::new();
+
+		let next = {
+			let mut heap = self.0.lock();
+			let timers = &mut heap.timers;
+
+			while let Some(Reverse(timer)) = timers.peek() {
+				if timer.deadline > now {
+					break;
+				}
+
+				if let Some(Reverse(timer)) = timers.pop() {
+					wakers.extend(timer.waker.upgrade().and_then(|waker| waker.lock().take()));
+				}
+			}
+
+			timers.peek().map(|Reverse(timer)| timer.deadline)
+		};
+
+		for waker in wakers {
+			waker.wake();
+		}
+
+		next
+	}
+}
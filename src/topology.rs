@@ -0,0 +1,89 @@
+//! What cores share with each other, used by
+//! [`StealPolicy::NearFirst`](crate::StealPolicy::NearFirst).
+
+/// Distance of cores that only share a NUMA node.
+const NODE: usize = 4;
+/// Distance of cores on different NUMA nodes.
+const REMOTE: usize = 5;
+
+/// Caches and NUMA node of a core, read from sysfs. Only available on Linux.
+#[derive(Debug)]
+pub(crate) struct Core {
+	/// Cache levels and the cores sharing each cache.
+	caches: Vec<(usize, Vec<usize>)>,
+	node: Option<usize>,
+}
+
+impl Core {
+	/// Reads the topology of core `id`, returns [`None`] if it isn't
+	/// available.
+	#[cfg(target_os = "linux")]
+	pub(crate) fn read(id: usize) -> Option<Self> {
+		use std::fs;
+
+		let cpu = format!("/sys/devices/system/cpu/cpu{}", id);
+
+		let caches: Vec<_> = fs::read_dir(format!("{}/cache", cpu))
+			.ok()?
+			.filter_map(Result::ok)
+			.filter(|entry| entry.file_name().to_string_lossy().starts_with("index"))
+			.filter_map(|entry| {
+				let read = |file| fs::read_to_string(entry.path().join(file)).ok();
+				let level = read("level")?.trim().parse().ok()?;
+				let cores = parse_list(&read("shared_cpu_list")?)?;
+
+				Some((level, cores))
+			})
+			.collect();
+
+		let node = fs::read_dir(&cpu)
+			.ok()?
+			.filter_map(Result::ok)
+			.find_map(|entry| {
+				entry
+					.file_name()
+					.to_str()?
+					.strip_prefix("node")?
+					.parse()
+					.ok()
+			});
+
+		(!caches.is_empty()).then(|| Self { caches, node })
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	pub(crate) fn read(_id: usize) -> Option<Self> {
+		None
+	}
+
+	/// Returns how far apart this core and core `id` are: the level of the
+	/// closest cache they share, or [`NODE`] and [`REMOTE`] if they share
+	/// none.
+	pub(crate) fn distance(&self, id: usize, other: &Self) -> usize {
+		self.caches
+			.iter()
+			.filter(|(_, cores)| cores.contains(&id))
+			.map(|(level, _)| *level)
+			.min()
+			.unwrap_or(match (self.node, other.node) {
+				(Some(node), Some(other)) if node == other => NODE,
+				_ => REMOTE,
+			})
+	}
+}
+
+/// Parses a list of cores like `0-3,8`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_list(list: &str) -> Option<Vec<usize>> {
+	let mut cores = Vec::new();
+
+	for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+		if let Some((start, end)) = range.split_once('-') {
+			cores.extend(start.parse::<usize>().ok()?..=end.parse().ok()?);
+		} else {
+			cores.push(range.parse().ok()?);
+		}
+	}
+
+	Some(cores)
+}
@@ -1,45 +1,65 @@
+mod park;
 mod queues;
 
 use std::{
-	cell::{Ref, RefCell, RefMut},
+	cell::{Cell, Ref, RefCell, RefMut},
 	future::Future,
 	iter::FromIterator,
-	sync::{
-		atomic::{AtomicUsize, Ordering},
-		Arc,
-	},
+	rc::Rc,
+	sync::Arc,
+	task::Poll,
 };
 
-use allochronic_channel::{broadcast, flag::Flag, mpmc};
-use allochronic_task::{LocalReceiver, LocalSender, Runnable};
+use allochronic_channel::{broadcast, flag::Flag};
+use allochronic_task::{LocalSender, Metadata, Runnable};
 use core_affinity::CoreId;
+use futures_util::{
+	future::{self, Pending},
+	FutureExt, StreamExt,
+};
 use once_cell::unsync::OnceCell;
+use park::Parker;
+pub(crate) use park::Sleepers;
 pub(crate) use queues::Runnables;
-use queues::{Group, Priority, Queue, Queues, Steal};
+use queues::{Group, Priority, Queue, Queues, Steal, Stealers, Victims};
 use vec_map::VecMap;
 
-use crate::{error, Executor};
+use crate::{error, metrics::Counters, Executor};
 
-type Sender = mpmc::Sender<Runnable>;
-type Receiver = mpmc::Receiver<Runnable>;
+type Deque = crossbeam_deque::Worker<Runnable>;
+
+/// Number of tasks in a row taken from the LIFO slot before it's pushed to
+/// the back of the queue, to not starve other tasks.
+const NEXT_LIMIT: u32 = 3;
 
 pub(crate) struct Worker {
 	pub(crate) executor: Arc<Executor>,
 	type_: Type,
-	pub(crate) injector: VecMap<VecMap<Sender>>,
-	local: VecMap<LocalSender>,
-	inner: Inner,
+	/// Own queues by priority and group, shared with `queue`.
+	pub(crate) injector: VecMap<VecMap<Rc<Deque>>>,
+	pub(crate) local: VecMap<LocalSender>,
+	/// LIFO slot, holding the last task woken up by another task on this
+	/// worker and it's queue.
+	next: Cell<Option<(Runnable, Rc<Deque>)>>,
 }
 
 enum Type {
 	Async {
+		index: usize,
+		inner: Inner,
+		counters: Arc<Counters>,
+		parker: Parker,
+		/// Tasks taken from the LIFO slot in a row.
+		streak: u32,
+		/// Tasks run so far, every [`Executor::injector_interval`]th pass
+		/// checks the global injectors first.
+		ticks: u32,
+		interval: u32,
 		queue: Priority<Group<Queue, Queues>>,
-		stealer: Priority<Group<Steal, Receiver>>,
-	},
-	Blocking {
-		count: AtomicUsize,
-		local: Priority<LocalReceiver>,
+		stealer: Priority<Group<Steal, Stealers>>,
 	},
+	/// Thread of the blocking pool, only used to access the [`Executor`].
+	Blocking,
 }
 
 struct Inner {
@@ -69,141 +89,420 @@ impl Worker {
 		Self::WORKER.with(|worker| fun(worker.get().and_then(|worker| worker.try_borrow().ok())))
 	}
 
+	/// Checks if the current thread is an async worker of `executor`.
+	pub(crate) fn is_current(executor: &Arc<Executor>) -> bool {
+		Self::try_with(|worker| {
+			worker.map_or(false, |worker| {
+				!worker.is_blocking() && Arc::ptr_eq(&worker.executor, executor)
+			})
+		})
+	}
+
+	pub(crate) const fn is_blocking(&self) -> bool {
+		matches!(self.type_, Type::Blocking)
+	}
+
 	pub(crate) fn with_mut<F: FnOnce(RefMut<'_, Self>) -> R, R>(fun: F) -> R {
 		Self::WORKER
 			.with(|worker| fun(worker.get().expect("`Worker` not initialized").borrow_mut()))
 	}
 
-	fn init(
-		executor: Arc<Executor>,
-		core: Option<CoreId>,
-		sender: Sender,
-		receiver: Receiver,
-		injector: Receiver,
-		stealer: Vec<Receiver>,
-	) {
+	fn init(executor: Arc<Executor>, index: usize, core: Option<CoreId>) {
 		if let Some(core) = core {
 			core_affinity::set_for_current(core);
 		}
 
 		let (local_sender, local_receiver) = allochronic_task::unbounded();
 
-		let type_ = Type::Async {
-			queue: Priority::new_queue(local_receiver, receiver),
-			stealer: Priority::new_stealer(injector, stealer),
-		};
-
+		// subscribe before syncing to not miss any queues registered in between
 		let inner = Inner {
 			shutdown: executor.shutdown.clone(),
 			management: executor.management(),
 		};
 
+		let counters = executor.counters(index);
+
+		let type_ = Type::Async {
+			index,
+			inner,
+			parker: Parker::new(&executor, Arc::clone(&counters)),
+			counters,
+			streak: 0,
+			ticks: 0,
+			interval: executor.injector_interval(),
+			queue: Priority::new_queue(local_receiver),
+			stealer: Priority::new(),
+		};
+
+		let mut worker = Self {
+			executor,
+			type_,
+			injector: VecMap::new(),
+			local: VecMap::from_iter(Some((0, local_sender))),
+			next: Cell::default(),
+		};
+		worker.sync();
+
+		Self::set(worker);
+	}
+
+	/// Initializes a thread of the blocking pool, which can spawn tasks but
+	/// doesn't run any.
+	pub(crate) fn init_blocking(executor: Arc<Executor>) {
+		Self::set(Self {
+			executor,
+			type_: Type::Blocking,
+			injector: VecMap::new(),
+			local: VecMap::new(),
+			next: Cell::default(),
+		});
+	}
+
+	fn set(worker: Self) {
 		Self::WORKER
-			.with(|worker| {
-				worker.set(RefCell::new(Self {
-					executor,
-					type_,
-					injector: VecMap::from_iter(Some((0, VecMap::from_iter(Some((0, sender)))))),
-					local: VecMap::from_iter(Some((0, local_sender))),
-					inner,
-				}))
-			})
+			.with(|cell| cell.set(RefCell::new(worker)))
 			.map_err(|_old| ())
 			.expect("`Worker` can't be initialized twice");
 	}
 
-	pub(crate) fn run<'w, S, F, R>(worker: &'w mut Self, select: S) -> Message<R>
+	/// Adds queues registered in the [`Executor`] that this worker doesn't
+	/// know about yet and drops queues of removed groups.
+	pub(crate) fn sync(&mut self) {
+		let (index, queue, stealer) = if let Type::Async {
+			index,
+			queue,
+			stealer,
+			..
+		} = &mut self.type_
+		{
+			(*index, queue, stealer)
+		} else {
+			return;
+		};
+
+		let executor = Arc::clone(&self.executor);
+		// ids of removed groups are reused, a queue that wasn't taken yet
+		// belongs to a new group
+		let removed: Vec<_> = {
+			let queues = executor.queues.read();
+
+			self.injector
+				.iter()
+				.flat_map(|(priority, groups)| groups.keys().map(move |group| (priority, group)))
+				.filter(|(priority, group)| {
+					queues
+						.get(*priority)
+						.and_then(|groups| groups.get(*group))
+						.map_or(true, |channels| channels.workers[index].0.lock().is_some())
+				})
+				.collect()
+		};
+
+		for (priority, group) in removed {
+			let deque = self
+				.injector
+				.get_mut(priority)
+				.and_then(|groups| groups.remove(group))
+				.expect("queue not found");
+
+			if let Some(queue_groups) = queue.groups(priority) {
+				queue_groups.remove(Queue::Group(group));
+
+				if queue_groups.is_empty() {
+					queue.remove(priority);
+				}
+			}
+
+			if let Some(stealer_groups) = stealer.groups(priority) {
+				stealer_groups.remove(Steal::Injector(group));
+				stealer_groups.remove(Steal::Stealer(group));
+
+				if stealer_groups.is_empty() {
+					stealer.remove(priority);
+				}
+			}
+
+			if let Some((runnable, next)) = self.next.take() {
+				if Rc::ptr_eq(&next, &deque) {
+					deque.push(runnable);
+				} else {
+					self.next.set(Some((runnable, next)));
+				}
+			}
+
+			// tasks spawned before this worker noticed the group was replaced
+			// belong to the new one, which keeps it alive
+			while let Some(runnable) = deque.pop() {
+				executor.register(priority, group);
+				executor.inject(priority, group, runnable);
+				executor.wake();
+			}
+		}
+
+		let queues = executor.queues.read();
+
+		for (priority, groups) in &*queues {
+			for (group, channels) in groups {
+				let injector = self.injector.entry(priority).or_insert_with(VecMap::new);
+
+				if injector.contains_key(group) || !executor.serves(group, index) {
+					continue;
+				}
+
+				let deque = Rc::new(
+					channels.workers[index]
+						.0
+						.lock()
+						.take()
+						.expect("worker queue already taken"),
+				);
+				injector.insert(group, Rc::clone(&deque));
+
+				if queue.groups(priority).is_none() {
+					queue.extend(priority, Group::new());
+				}
+
+				queue
+					.groups(priority)
+					.expect("priority not found")
+					.extend(Queue::Group(group), Some(Queues::Group(Rc::clone(&deque))));
+
+				if stealer.groups(priority).is_none() {
+					stealer.extend(priority, Group::new());
+				}
+
+				let stealer = stealer.groups(priority).expect("priority not found");
+				stealer.extend(
+					Steal::Injector(group),
+					Some(Stealers::Injector(channels.injector.1.clone())),
+				);
+				let victims = channels
+					.workers
+					.iter()
+					.enumerate()
+					.filter(|(worker, _)| *worker != index && executor.serves(group, *worker))
+					.map(|(worker, (_, victim))| (executor.distance(index, worker), victim.clone()))
+					.collect();
+				stealer.extend(
+					Steal::Stealer(group),
+					Some(Stealers::Workers(Victims::new(
+						victims,
+						deque,
+						executor.steal(),
+					))),
+				);
+			}
+		}
+	}
+
+	/// Waits for the next [`Message`], `blocked` being the task the caller
+	/// blocks on, if any. Sources are polled in order, `blocked` first.
+	pub(crate) fn run<B>(worker: &mut Self, mut blocked: Option<&mut B>) -> Message<B::Output>
 	where
-		S: FnOnce(
-			&'w mut Flag,
-			&'w mut broadcast::Receiver<()>,
-			&'w mut Priority<Group<Queue, Queues>>,
-			&'w mut Priority<Group<Steal, Receiver>>,
-		) -> F,
-		F: Future<Output = Option<Message<R>>>,
+		B: Future + Unpin,
 	{
-		futures_lite::future::block_on(async move {
-			let Worker {
+		let Self {
+			executor,
+			type_,
+			next,
+			..
+		} = worker;
+		let (shutdown, management, counters, parker, streak, ticks, interval, queue, stealer) =
+			if let Type::Async {
 				inner: Inner {
 					shutdown,
 					management,
 				},
-				type_,
+				counters,
+				parker,
+				streak,
+				ticks,
+				interval,
+				queue,
+				stealer,
 				..
-			} = worker;
-			let (stealer, queue) = if let Type::Async { stealer, queue } = type_ {
-				(stealer, queue)
+			} = type_
+			{
+				(
+					shutdown, management, counters, parker, streak, ticks, *interval, queue,
+					stealer,
+				)
 			} else {
 				unreachable!("`Worker` is not async")
 			};
+		// under constant local load the global injectors would never be reached
+		let fair = *ticks % interval == 0;
 
-			if let Some(message) = select(shutdown, management, queue, stealer).await {
-				message
-			} else {
-				unreachable!("a `Sender` dropped");
+		// a single pass over all sources that doesn't wake itself, letting the
+		// `Parker` decide how to wait, the budget of a task blocking on another
+		// one doesn't apply to the worker
+		let message = allochronic_util::coop::unconstrained(|| {
+			parker.block_on(
+				executor,
+				future::poll_fn(|cx| {
+					if let Some(Poll::Ready(result)) =
+						blocked.as_mut().map(|blocked| blocked.poll_unpin(cx))
+					{
+						Poll::Ready(Message::Blocked(result))
+					} else if shutdown.poll_unpin(cx).is_ready() {
+						Poll::Ready(Message::Shutdown)
+					} else if let Poll::Ready(Some(())) = management.poll_next_unpin(cx) {
+						Poll::Ready(Message::Management(()))
+					} else if let Some(Poll::Ready(Some(task))) =
+						fair.then(|| stealer.poll_injectors(cx))
+					{
+						Poll::Ready(Message::Task(task))
+					} else if let Some(runnable) = Self::next(next, streak) {
+						Poll::Ready(Message::Task(Runnables::Group(runnable)))
+					} else if let Poll::Ready(Some(task)) = queue.poll_next_unpin(cx) {
+						Poll::Ready(Message::Task(task))
+					} else if let Poll::Ready(Some(task)) = stealer.poll_next_unpin(cx) {
+						Poll::Ready(Message::Task(task))
+					} else {
+						Poll::Pending
+					}
+				}),
+			)
+		});
+
+		if let Message::Task(runnable) = &message {
+			*ticks = ticks.wrapping_add(1);
+			counters.polled(runnable.source());
+			#[cfg(feature = "tracing")]
+			tracing::trace!(source = ?runnable.source(), "running task");
+		}
+
+		message
+	}
+
+	/// Takes the task in the LIFO slot, unless `streak` reached
+	/// [`NEXT_LIMIT`], pushing it to the back of it's queue instead.
+	fn next(next: &Cell<Option<(Runnable, Rc<Deque>)>>, streak: &mut u32) -> Option<Runnable> {
+		match next.take() {
+			Some((runnable, _)) if *streak < NEXT_LIMIT => {
+				*streak += 1;
+				Some(runnable)
 			}
+			Some((runnable, deque)) => {
+				deque.push(runnable);
+				*streak = 0;
+				None
+			}
+			None => {
+				*streak = 0;
+				None
+			}
+		}
+	}
+
+	/// Checks if the current thread is a worker of `executor` that picked up
+	/// the queue of `priority` and `group`.
+	pub(crate) fn has_queue(executor: &Arc<Executor>, priority: usize, group: usize) -> bool {
+		Self::try_with(|worker| {
+			worker.map_or(false, |worker| {
+				Arc::ptr_eq(&worker.executor, executor)
+					&& worker
+						.injector
+						.get(priority)
+						.map_or(false, |groups| groups.contains_key(group))
+			})
 		})
 	}
 
-	pub(crate) fn start(
-		executor: Arc<Executor>,
-		core: Option<CoreId>,
-		sender: Sender,
-		receiver: Receiver,
-		injector: Receiver,
-		stealer: Vec<Receiver>,
-	) {
-		Self::init(executor, core, sender, receiver, injector, stealer);
+	/// Schedules `runnable` on this worker, returns it if the queue of
+	/// `priority` and `group` wasn't picked up yet. Otherwise returns if other
+	/// workers can steal it.
+	///
+	/// `woken` tasks of priority `0` go into the LIFO slot, to be run next.
+	/// Tasks waking themselves are yielding and go to the back of the queue.
+	pub(crate) fn schedule(
+		&self,
+		priority: usize,
+		group: usize,
+		runnable: Runnable,
+		woken: bool,
+	) -> Result<bool, Runnable> {
+		let deque = if let Some(deque) = self
+			.injector
+			.get(priority)
+			.and_then(|groups| groups.get(group))
+		{
+			deque
+		} else {
+			return Err(runnable);
+		};
+
+		if woken && priority == 0 && allochronic_task::current() != Some(runnable.metadata().id()) {
+			if let Some((previous, deque)) = self.next.replace(Some((runnable, Rc::clone(deque)))) {
+				deque.push(previous);
+				Ok(true)
+			} else {
+				Ok(false)
+			}
+		} else {
+			deque.push(runnable);
+			Ok(true)
+		}
+	}
+
+	/// Drops all queued tasks, cancelling them.
+	pub(crate) fn clear(&mut self) {
+		drop(self.next.take());
+
+		if let Type::Async { queue, stealer, .. } = &mut self.type_ {
+			futures_lite::future::block_on(async move {
+				while let Poll::Ready(Some(runnable)) = allochronic_util::poll(queue.next()).await {
+					drop(runnable);
+				}
+
+				while let Poll::Ready(Some(runnable)) = allochronic_util::poll(stealer.next()).await
+				{
+					drop(runnable);
+				}
+			});
+		}
+	}
+
+	pub(crate) fn start(executor: Arc<Executor>, index: usize, core: Option<CoreId>) {
+		#[cfg(feature = "tracing")]
+		let _span = tracing::debug_span!("worker", index).entered();
+
+		Self::init(executor, index, core);
 
 		Self::WORKER.with(|worker| {
 			let worker = worker.get().expect("`Worker` not initialized");
 
 			loop {
-				let message = Self::run(
-					&mut *worker.borrow_mut(),
-					|shutdown, management, queue, stealer| async move {
-						allochronic_util::select!(
-							_: shutdown => Some(Message::Shutdown),
-							management: management => management.map(Message::Management),
-							task: queue => task.map(Message::Task),
-							task: stealer => task.map(Runnables::Group).map(Message::Task),
-						)
-					},
-				);
+				let message = Self::run(&mut *worker.borrow_mut(), None::<&mut Pending<()>>);
 
 				match message {
 					Message::Blocked(()) => unreachable!("returned `main` in wrong function"),
 					Message::Shutdown => break,
-					Message::Management(()) => (),
+					Message::Management(()) => worker.borrow_mut().sync(),
 					Message::Task(runnable) => {
 						runnable.run();
 					}
 				}
-
-				{
-					let executor = &worker.borrow().executor;
-
-					if executor.tasks.load(Ordering::Relaxed) == 0 {
-						executor.finished.notify();
-					}
-				}
 			}
+
+			worker.borrow_mut().clear();
 		});
 	}
 
 	pub(crate) fn start_with<M, R>(
 		executor: Arc<Executor>,
+		index: usize,
 		core: Option<CoreId>,
-		sender: Sender,
-		receiver: Receiver,
-		injector: Receiver,
-		stealer: Vec<Receiver>,
 		main: M,
 	) -> Result<R, error::Executor>
 	where
 		M: Future<Output = R>,
 	{
-		Self::init(executor, core, sender, receiver, injector, stealer);
+		#[cfg(feature = "tracing")]
+		let _span = tracing::debug_span!("worker", index).entered();
+
+		let metadata = Metadata::new(executor.next_id(), None);
+		Self::init(executor, index, core);
 
 		Self::WORKER
 			.with(|worker| {
@@ -214,46 +513,22 @@ impl Worker {
 					.get(0)
 					.expect("initial group doesn't exist")
 					.clone();
-				allochronic_task::block_on_local(main, sender, |runnable, mut task| {
+				allochronic_task::block_on_local(main, metadata, sender, |runnable, mut task| {
 					runnable.schedule();
 
 					loop {
-						let message = {
-							let worker = &mut *worker.borrow_mut();
-							Self::run(worker, |shutdown, management, queue, stealer| {
-								let task = &mut task;
-								async move {
-									allochronic_util::select!(
-										result: task => Some(Message::Blocked(result)),
-										_: shutdown => Some(Message::Shutdown),
-										management: management => {
-											management.map(Message::Management)
-										},
-										task: queue => task.map(Message::Task),
-										task: stealer => {
-											task.map(Runnables::Group).map(Message::Task)
-										},
-									)
-								}
-							})
-						};
+						let message = Self::run(&mut *worker.borrow_mut(), Some(&mut task));
 
 						match message {
 							Message::Blocked(result) => break result,
-							Message::Shutdown => break task.cancel(),
-							Message::Management(()) => (),
+							Message::Shutdown => {
+								break task.cancel_with(|| worker.borrow_mut().clear());
+							}
+							Message::Management(()) => worker.borrow_mut().sync(),
 							Message::Task(runnable) => {
 								runnable.run();
 							}
 						}
-
-						{
-							let executor = &worker.borrow().executor;
-
-							if executor.tasks.load(Ordering::Relaxed) == 0 {
-								executor.finished.notify();
-							}
-						}
 					}
 				})
 			})
@@ -0,0 +1,233 @@
+use std::{
+	future::Future,
+	hint,
+	pin::pin,
+	sync::{
+		atomic::{self, AtomicBool, AtomicUsize, Ordering},
+		Arc,
+	},
+	task::{Context, Poll, Wake, Waker},
+	thread::{self, Thread},
+	time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use crate::{metrics::Counters, Executor, Idle};
+
+/// Number of passes after which a busy worker checks for I/O events.
+const IO_INTERVAL: u32 = 61;
+/// Number of passes after which a busy worker fires due timers.
+const TIMER_INTERVAL: u32 = 31;
+
+/// Parks a worker on the [`Reactor`](crate::reactor::Reactor) or the thread
+/// while it waits for tasks, I/O events or timers.
+pub(crate) struct Parker {
+	unparker: Arc<Unparker>,
+	waker: Waker,
+	idle: Idle,
+	ticks: u32,
+	counters: Arc<Counters>,
+}
+
+/// Wakes up a parked worker.
+#[derive(Debug)]
+struct Unparker {
+	thread: Thread,
+	reactor: Arc<mio::Waker>,
+	/// The worker is polling the reactor instead of parking the thread.
+	polling: AtomicBool,
+	/// The worker was woken up since it last polled.
+	notified: AtomicBool,
+}
+
+/// Parked workers of an [`Executor`], woken up one at a time when tasks are
+/// scheduled instead of waking up all of them.
+#[derive(Debug, Default)]
+pub(crate) struct Sleepers {
+	sleepers: Mutex<Vec<Arc<Unparker>>>,
+	/// Number of `sleepers`, checked before locking them.
+	len: AtomicUsize,
+}
+
+impl Wake for Unparker {
+	fn wake(self: Arc<Self>) {
+		self.wake_by_ref();
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		self.notified.store(true, Ordering::SeqCst);
+
+		if self.polling.load(Ordering::SeqCst) {
+			// a failed wake up only causes a spurious poll
+			let _result = self.reactor.wake();
+		} else {
+			self.thread.unpark();
+		}
+	}
+}
+
+impl Sleepers {
+	fn add(&self, unparker: &Arc<Unparker>) {
+		{
+			let mut sleepers = self.sleepers.lock();
+			sleepers.push(Arc::clone(unparker));
+			self.len.store(sleepers.len(), Ordering::SeqCst);
+		}
+
+		// pairs with the fence in `wake_one`, either the worker sees the task
+		// when polling again or the task sees the worker
+		atomic::fence(Ordering::SeqCst);
+	}
+
+	fn remove(&self, unparker: &Arc<Unparker>) {
+		let mut sleepers = self.sleepers.lock();
+		sleepers.retain(|sleeper| !Arc::ptr_eq(sleeper, unparker));
+		self.len.store(sleepers.len(), Ordering::SeqCst);
+	}
+
+	/// Wakes up the worker that parked last, if any.
+	pub(crate) fn wake_one(&self) {
+		atomic::fence(Ordering::SeqCst);
+
+		if self.len.load(Ordering::SeqCst) == 0 {
+			return;
+		}
+
+		let sleeper = {
+			let mut sleepers = self.sleepers.lock();
+			let sleeper = sleepers.pop();
+			self.len.store(sleepers.len(), Ordering::SeqCst);
+			sleeper
+		};
+
+		if let Some(sleeper) = sleeper {
+			Wake::wake(sleeper);
+		}
+	}
+}
+
+impl Parker {
+	pub(crate) fn new(executor: &Executor, counters: Arc<Counters>) -> Self {
+		let unparker = Arc::new(Unparker {
+			thread: thread::current(),
+			reactor: executor.reactor.waker(),
+			polling: AtomicBool::new(false),
+			notified: AtomicBool::new(false),
+		});
+
+		Self {
+			waker: Waker::from(Arc::clone(&unparker)),
+			unparker,
+			idle: executor.idle(),
+			ticks: 0,
+			counters,
+		}
+	}
+
+	/// Polls `future` until it's ready, waiting according to [`Idle`] in
+	/// between. Fires timers of `executor` and waits for I/O events while
+	/// parked.
+	pub(crate) fn block_on<F: Future>(&mut self, executor: &Executor, future: F) -> F::Output {
+		let mut future = pin!(future);
+		let mut cx = Context::from_waker(&self.waker);
+		let mut spins = 0;
+		let mut registered = false;
+
+		Self::tick(&mut self.ticks, executor);
+
+		loop {
+			self.unparker.notified.store(false, Ordering::SeqCst);
+
+			if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+				if registered {
+					executor.sleepers.remove(&self.unparker);
+				}
+
+				break output;
+			}
+
+			let timeout = executor
+				.timers
+				.fire()
+				.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+			match self.idle {
+				Idle::BusyPoll => Self::tick(&mut self.ticks, executor),
+				Idle::SpinThenPark(max) if spins < max => {
+					spins += 1;
+					hint::spin_loop();
+					Self::tick(&mut self.ticks, executor);
+				}
+				// worker queues don't wake up workers themselves, register and poll
+				// again to not miss tasks scheduled in between
+				Idle::SpinThenPark(_) | Idle::Park if !registered => {
+					executor.sleepers.add(&self.unparker);
+					registered = true;
+				}
+				Idle::SpinThenPark(_) | Idle::Park => {
+					self.park(executor, timeout);
+					registered = false;
+				}
+			}
+		}
+	}
+
+	/// Busy workers never park, fires timers and checks for I/O events
+	/// regularly.
+	fn tick(ticks: &mut u32, executor: &Executor) {
+		*ticks = ticks.wrapping_add(1);
+
+		if *ticks % TIMER_INTERVAL == 0 {
+			let _next = executor.timers.fire();
+		}
+
+		if *ticks % IO_INTERVAL == 0 {
+			if let Some(mut polling) = executor.reactor.try_lock() {
+				polling.poll(Some(Duration::ZERO));
+			}
+		}
+	}
+
+	/// Waits for a wake up, I/O events or `timeout`. The worker has to be
+	/// registered in [`Sleepers`] already.
+	fn park(&self, executor: &Executor, timeout: Option<Duration>) {
+		let unparker = &self.unparker;
+		let sleepers = &executor.sleepers;
+		let parked = Instant::now();
+
+		if unparker.notified.load(Ordering::SeqCst) {
+			sleepers.remove(unparker);
+			return;
+		}
+
+		if let Some(mut polling) = executor.reactor.try_lock() {
+			unparker.polling.store(true, Ordering::SeqCst);
+
+			// wake ups before `polling` was set only unparked the thread, don't wait
+			// for I/O events then
+			if unparker.notified.load(Ordering::SeqCst) {
+				polling.poll(Some(Duration::ZERO));
+			} else {
+				polling.poll(timeout);
+			}
+
+			unparker.polling.store(false, Ordering::SeqCst);
+			drop(polling);
+			sleepers.remove(unparker);
+
+			// keep polling while this worker is busy
+			sleepers.wake_one();
+		} else {
+			if let Some(timeout) = timeout {
+				thread::park_timeout(timeout);
+			} else {
+				thread::park();
+			}
+
+			sleepers.remove(unparker);
+		}
+
+		self.counters.parked(parked.elapsed());
+	}
+}
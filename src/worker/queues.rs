@@ -1,8 +1,8 @@
 use std::{
-	array::IntoIter,
 	collections::VecDeque,
 	iter::FromIterator,
 	pin::Pin,
+	rc::Rc,
 	task::{Context, Poll},
 };
 
@@ -11,7 +11,11 @@ use allochronic_task::{LocalReceiver, LocalRunnable, Runnable};
 use futures_util::{Stream, StreamExt};
 use vec_map::VecMap;
 
+use crate::{coop, metrics::Source, StealPolicy};
+
 type Receiver = mpmc::Receiver<Runnable>;
+type Deque = crossbeam_deque::Worker<Runnable>;
+type Stealer = crossbeam_deque::Stealer<Runnable>;
 
 pub(crate) struct Priority<S: Stream>(VecMap<S>);
 
@@ -34,40 +38,24 @@ impl<S: Stream + Unpin> Stream for Priority<S> {
 }
 
 impl Priority<Group<Queue, Queues>> {
-	pub(super) fn new_queue(local: LocalReceiver, queue: Receiver) -> Self {
+	pub(super) fn new_queue(local: LocalReceiver) -> Self {
 		Self(VecMap::from_iter(Some((
 			0,
 			Group(
-				IntoIter::new([
-					(Queue::Local, Queues::Local(local)),
-					(Queue::Group(0), Queues::Group(queue)),
-				])
-				.collect(),
+				Some((Queue::Local, Queues::Local(local)))
+					.into_iter()
+					.collect(),
 			),
 		))))
 	}
 }
 
-impl Priority<Group<Steal, Receiver>> {
-	pub(super) fn new_stealer(injector: Receiver, stealer: Vec<Receiver>) -> Self {
-		let injector = Some((Steal::Injector(0), injector)).into_iter();
-		let stealer = stealer
-			.into_iter()
-			.map(|stealer| (Steal::Stealer(0), stealer));
-
-		Self(VecMap::from_iter(Some((
-			0,
-			Group(injector.chain(stealer).collect()),
-		))))
+impl<S: Stream> Priority<S> {
+	pub(super) fn new() -> Self {
+		Self(VecMap::new())
 	}
-}
 
-impl<S: Stream<Item = Runnable>> Priority<S> {
-	pub(super) fn extend<T: IntoIterator<Item = Receiver>>(
-		&mut self,
-		priority: usize,
-		receiver: S,
-	) {
+	pub(super) fn extend(&mut self, priority: usize, receiver: S) {
 		self.0.insert(priority, receiver);
 	}
 
@@ -114,6 +102,29 @@ impl<I: Copy + PartialEq + Unpin, S: Stream + Unpin> Stream for Group<I, S> {
 	}
 }
 
+impl Priority<Group<Steal, Stealers>> {
+	/// Polls only the global injectors, skipping other workers' queues.
+	pub(super) fn poll_injectors(&mut self, cx: &mut Context<'_>) -> Poll<Option<Runnables>> {
+		let mut result = Poll::Ready(None);
+
+		for group in self.0.values_mut() {
+			for (_, receiver) in group
+				.0
+				.iter_mut()
+				.filter(|(id, _)| matches!(id, Steal::Injector(_)))
+			{
+				match receiver.poll_next_unpin(cx) {
+					Poll::Ready(Some(runnable)) => return Poll::Ready(Some(runnable)),
+					Poll::Ready(None) => (),
+					Poll::Pending => result = Poll::Pending,
+				}
+			}
+		}
+
+		result
+	}
+}
+
 impl<I: Copy + PartialEq + Unpin, S: Stream + Unpin> Group<I, S> {
 	pub(super) fn new() -> Self {
 		Self(VecDeque::default())
@@ -127,13 +138,37 @@ impl<I: Copy + PartialEq + Unpin, S: Stream + Unpin> Group<I, S> {
 	pub(super) fn remove(&mut self, id: I) {
 		self.0.retain(|(index, _)| *index != id);
 	}
+
+	pub(super) fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
 }
 
+/// Queues owned by a worker. Unlike channels, the work-stealing
+/// [`Deque`](crossbeam_deque::Worker)s don't register wakers, workers are
+/// woken up through [`Sleepers`](super::Sleepers) instead.
 pub(crate) enum Queues {
-	Group(Receiver),
+	Group(Rc<Deque>),
 	Local(LocalReceiver),
 }
 
+pub(crate) enum Stealers {
+	Injector(Receiver),
+	Workers(Victims),
+}
+
+/// Queues of other workers of the same priority and group, stolen from in
+/// the order given by [`StealPolicy`].
+pub(crate) struct Victims {
+	/// Ordered by distance for [`StealPolicy::NearFirst`].
+	victims: Vec<Stealer>,
+	/// Own queue stolen tasks are moved into.
+	deque: Rc<Deque>,
+	policy: StealPolicy,
+	/// Victim to start with for [`StealPolicy::RoundRobin`].
+	next: usize,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub(crate) enum Queue {
 	Group(usize),
@@ -146,9 +181,12 @@ pub(crate) enum Steal {
 	Stealer(usize),
 }
 
+/// Runnable and the queue it was taken from.
 pub(crate) enum Runnables {
 	Local(LocalRunnable),
 	Group(Runnable),
+	Injector(Runnable),
+	Stolen(Runnable),
 }
 
 impl Stream for Queues {
@@ -156,9 +194,9 @@ impl Stream for Queues {
 
 	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
 		match self.get_mut() {
-			Queues::Group(task) => task
-				.poll_next_unpin(cx)
-				.map(|option| option.map(Runnables::Group)),
+			Queues::Group(deque) => deque.pop().map_or(Poll::Pending, |runnable| {
+				Poll::Ready(Some(Runnables::Group(runnable)))
+			}),
 			Queues::Local(task) => task
 				.poll_next_unpin(cx)
 				.map(|option| option.map(Runnables::Local)),
@@ -166,13 +204,157 @@ impl Stream for Queues {
 	}
 }
 
+impl Stream for Stealers {
+	type Item = Runnables;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		match self.get_mut() {
+			Stealers::Injector(task) => task
+				.poll_next_unpin(cx)
+				.map(|option| option.map(Runnables::Injector)),
+			Stealers::Workers(victims) => victims.steal().map_or(Poll::Pending, |runnable| {
+				Poll::Ready(Some(Runnables::Stolen(runnable)))
+			}),
+		}
+	}
+}
+
+impl Victims {
+	/// `victims` are the queues of other workers and their distance to this
+	/// one, `deque` the own queue.
+	pub(super) fn new(
+		mut victims: Vec<(usize, Stealer)>,
+		deque: Rc<Deque>,
+		policy: StealPolicy,
+	) -> Self {
+		victims.sort_by_key(|(distance, _)| *distance);
+
+		Self {
+			victims: victims.into_iter().map(|(_, victim)| victim).collect(),
+			deque,
+			policy,
+			next: 0,
+		}
+	}
+
+	/// Steals half of the first victim's queue that has any tasks, moving
+	/// them into the own queue and returning one of them.
+	fn steal(&mut self) -> Option<Runnable> {
+		let len = self.victims.len();
+
+		let start = match self.policy {
+			_ if len == 0 => return None,
+			StealPolicy::Random => fastrand::usize(..len),
+			StealPolicy::RoundRobin => {
+				let start = self.next;
+				self.next = (start + 1) % len;
+				start
+			}
+			StealPolicy::NearFirst => 0,
+		};
+
+		(0..len).find_map(|offset| {
+			let victim = &self.victims[(start + offset) % len];
+
+			loop {
+				match victim.steal_batch_and_pop(&self.deque) {
+					crossbeam_deque::Steal::Success(runnable) => break Some(runnable),
+					crossbeam_deque::Steal::Empty => break None,
+					// lost a race against another thief or the owner
+					crossbeam_deque::Steal::Retry => (),
+				}
+			}
+		})
+	}
+}
+
 impl Runnables {
-	pub(crate) fn run(self) {
+	pub(crate) const fn source(&self) -> Source {
 		match self {
+			Runnables::Local(_) => Source::Local,
+			Runnables::Group(_) => Source::Group,
+			Runnables::Injector(_) => Source::Injector,
+			Runnables::Stolen(_) => Source::Stolen,
+		}
+	}
+
+	/// Runs the task with a fresh [`coop`] budget.
+	pub(crate) fn run(self) {
+		allochronic_util::coop::budget(coop::BUDGET, || match self {
 			Runnables::Local(task) => task.run(),
-			Runnables::Group(task) => {
+			Runnables::Group(task) | Runnables::Injector(task) | Runnables::Stolen(task) => {
 				task.run();
 			}
-		}
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::rc::Rc;
+
+	use allochronic_task::{Metadata, Runnable};
+
+	use super::{Deque, Victims};
+	use crate::StealPolicy;
+
+	/// Steals from victims at the given distances, each holding two tasks,
+	/// and returns the number of tasks left in each victim after every steal.
+	fn steal(policy: StealPolicy, distances: [usize; 3]) -> Vec<[usize; 3]> {
+		let queues: Vec<Deque> = distances
+			.iter()
+			.map(|_| {
+				let deque = Deque::new_fifo();
+
+				for id in 0..2 {
+					let (runnable, task) = allochronic_task::spawn(
+						async {},
+						Metadata::new(id, None),
+						drop::<Runnable>,
+					);
+					task.detach();
+					deque.push(runnable);
+				}
+
+				deque
+			})
+			.collect();
+
+		let mut victims = Victims::new(
+			distances
+				.iter()
+				.zip(&queues)
+				.map(|(distance, deque)| (*distance, deque.stealer()))
+				.collect(),
+			Rc::new(Deque::new_fifo()),
+			policy,
+		);
+
+		(0..3)
+			.map(|_| {
+				assert!(victims.steal().is_some(), "nothing stolen");
+				[queues[0].len(), queues[1].len(), queues[2].len()]
+			})
+			.collect()
+	}
+
+	#[test]
+	fn round_robin() {
+		// victims are visited in turn, regardless of distance
+		assert_eq!(steal(StealPolicy::RoundRobin, [0, 0, 0]), [
+			[1, 2, 2],
+			[1, 1, 2],
+			[1, 1, 1]
+		]);
+	}
+
+	#[test]
+	fn near_first() {
+		// the closest victim is emptied first, then the next closest
+		assert_eq!(steal(StealPolicy::NearFirst, [2, 0, 1]), [
+			[2, 1, 2],
+			[2, 0, 2],
+			[2, 0, 1]
+		]);
 	}
 }
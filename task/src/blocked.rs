@@ -10,13 +10,13 @@ use std::{
 };
 
 use async_task::Task;
-use futures_util::FutureExt;
+use futures_util::{future, FutureExt};
 
-use crate::{error, LocalRunnable, LocalSender, Runnable};
+use crate::{error, LocalRunnable, LocalSender, Metadata, Runnable};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
-pub struct BlockedTask<R>(Rc<RefCell<Option<Task<Finished<R>>>>>);
+pub struct BlockedTask<R>(Rc<RefCell<Option<Task<Finished<R>, Metadata>>>>);
 
 #[derive(Debug)]
 pub struct Finished<R>(Inner<R>);
@@ -48,9 +48,37 @@ impl<R> BlockedTask<R> {
 
 		Finished(Inner::Cancelled)
 	}
+
+	/// Cancels the task, calling `run` until it was dropped. This is necessary
+	/// if the task's scheduler doesn't drop it on its own, like when it's
+	/// queued in a [`LocalSender`].
+	pub fn cancel_with<F: FnMut()>(self, mut run: F) -> Finished<R> {
+		let task = self.0.borrow_mut().take();
+
+		if let Some(task) = task {
+			let mut cancel = Box::pin(task.cancel());
+
+			futures_lite::future::block_on(future::poll_fn(|cx| {
+				let poll = cancel.as_mut().poll(cx);
+
+				if poll.is_pending() {
+					run();
+				}
+
+				poll.map(drop)
+			}));
+		}
+
+		Finished(Inner::Cancelled)
+	}
 }
 
-pub fn block_on<F, S, M>(future: F, schedule: S, main: M) -> Result<F::Output, error::Cancelled>
+pub fn block_on<F, S, M>(
+	future: F,
+	metadata: Metadata,
+	schedule: S,
+	main: M,
+) -> Result<F::Output, error::Cancelled>
 where
 	F: Future + Send,
 	F::Output: Send,
@@ -58,12 +86,14 @@ where
 	M: FnOnce(Runnable, BlockedTask<F::Output>) -> Finished<F::Output>,
 {
 	let (runnable, task) = unsafe {
-		async_task::spawn_unchecked(
-			async move { Finished(Inner::Output(future.await)) },
-			move |runnable| {
-				schedule(runnable.into());
-			},
-		)
+		async_task::Builder::new()
+			.metadata(metadata)
+			.spawn_unchecked(
+				|_| async move { Finished(Inner::Output(future.await)) },
+				move |runnable: async_task::Runnable<Metadata>| {
+					schedule(runnable.into());
+				},
+			)
 	};
 	let task = Rc::new(RefCell::new(Some(task)));
 	let result = {
@@ -92,6 +122,7 @@ where
 
 pub fn block_on_local<F, M>(
 	future: F,
+	metadata: Metadata,
 	sender: LocalSender,
 	main: M,
 ) -> Result<F::Output, error::Cancelled>
@@ -100,12 +131,14 @@ where
 	M: FnOnce(LocalRunnable, BlockedTask<F::Output>) -> Finished<F::Output>,
 {
 	let (runnable, task) = unsafe {
-		async_task::spawn_unchecked(
-			async move { Finished(Inner::Output(future.await)) },
-			move |runnable| {
-				sender.send(LocalRunnable::new(runnable));
-			},
-		)
+		async_task::Builder::new()
+			.metadata(metadata)
+			.spawn_unchecked(
+				|_| async move { Finished(Inner::Output(future.await)) },
+				move |runnable| {
+					sender.send(LocalRunnable::new(runnable));
+				},
+			)
 	};
 	let runnable = LocalRunnable::new(runnable);
 	let task = Rc::new(RefCell::new(Some(task)));
@@ -29,6 +29,10 @@ impl LocalSender {
 		self.0.send(item);
 	}
 
+	pub fn try_send(&self, item: LocalRunnable) -> Result<(), LocalRunnable> {
+		self.0.try_send(item)
+	}
+
 	#[must_use]
 	pub fn is_empty(&self) -> bool {
 		self.0.is_empty()
@@ -63,9 +63,11 @@ mod blocked;
 mod channel;
 pub mod error;
 mod local;
+mod metadata;
 mod task;
 
 pub use blocked::{block_on, block_on_local, BlockedTask, Finished};
 pub use channel::{unbounded, LocalReceiver, LocalSender};
 pub use local::LocalRunnable;
-pub use task::{spawn, Runnable, Task};
+pub use metadata::{current, Metadata};
+pub use task::{spawn, spawn_local, spawn_unchecked, Runnable, Task};
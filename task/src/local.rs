@@ -2,17 +2,20 @@ use std::marker::PhantomData;
 
 use async_task::Runnable;
 
+use crate::{metadata, Metadata};
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
-pub struct LocalRunnable(Runnable, PhantomData<*const ()>);
+pub struct LocalRunnable(Runnable<Metadata>, PhantomData<*const ()>);
 
 impl LocalRunnable {
-	pub(crate) fn new(runnable: Runnable) -> Self {
+	pub(crate) fn new(runnable: Runnable<Metadata>) -> Self {
 		Self(runnable, PhantomData)
 	}
 
 	pub fn run(self) {
-		self.0.run();
+		let id = self.0.metadata().id();
+		metadata::enter(id, || self.0.run());
 	}
 
 	pub fn schedule(self) {
@@ -0,0 +1,51 @@
+use std::{cell::Cell, sync::Arc};
+
+thread_local!(static CURRENT: Cell<Option<u64>> = Cell::new(None));
+
+/// Identity of a task, stored alongside it by [`async_task`].
+#[derive(Clone, Debug)]
+pub struct Metadata {
+	id: u64,
+	name: Option<Arc<str>>,
+}
+
+/// Restores the previously running task when dropped, even when unwinding.
+struct Reset(Option<u64>);
+
+impl Drop for Reset {
+	fn drop(&mut self) {
+		CURRENT.with(|current| current.set(self.0));
+	}
+}
+
+impl Metadata {
+	#[must_use]
+	pub fn new(id: u64, name: Option<String>) -> Self {
+		Self {
+			id,
+			name: name.map(Arc::from),
+		}
+	}
+
+	#[must_use]
+	pub const fn id(&self) -> u64 {
+		self.id
+	}
+
+	#[must_use]
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+}
+
+/// Marks task `id` as running on the current thread while calling `run`.
+pub(crate) fn enter<R, F: FnOnce() -> R>(id: u64, run: F) -> R {
+	let _reset = Reset(CURRENT.with(|current| current.replace(Some(id))));
+	run()
+}
+
+/// Returns the id of the task running on the current thread, if there is any.
+#[must_use]
+pub fn current() -> Option<u64> {
+	CURRENT.with(Cell::get)
+}
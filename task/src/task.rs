@@ -1,23 +1,43 @@
 use std::{
 	future::Future,
+	mem,
 	pin::Pin,
 	task::{Context, Poll},
 };
 
 use futures_util::FutureExt;
 
+use crate::{error, metadata, LocalRunnable, LocalSender, Metadata};
+
 #[derive(Debug)]
-pub struct Task<R>(Option<async_task::Task<R>>);
+pub struct Task<R> {
+	task: Option<async_task::FallibleTask<R, Metadata>>,
+	/// Copy of the [`Metadata`], which [`async_task::FallibleTask`] doesn't
+	/// give access to.
+	metadata: Metadata,
+	cancel_on_drop: bool,
+}
 
 #[derive(Debug)]
-pub struct Runnable(async_task::Runnable);
+pub struct Runnable(async_task::Runnable<Metadata>);
+
+/// Moves a [`LocalSender`] into a scheduler, which can be called from any
+/// thread.
+struct Scheduler(LocalSender);
+
+// SAFETY: the underlying channel is thread-safe, `LocalRunnable`s sent through
+// it are only run by the thread that spawned them, which
+// `async_task::spawn_local` checks.
+unsafe impl Send for Scheduler {}
+unsafe impl Sync for Scheduler {}
 
 impl<R> Future for Task<R> {
-	type Output = R;
+	type Output = Result<R, error::Cancelled>;
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		if let Some(task) = self.0.as_mut() {
+		if let Some(task) = self.task.as_mut() {
 			task.poll_unpin(cx)
+				.map(|output| output.ok_or(error::Cancelled))
 		} else {
 			unreachable!("`Task` polled after drop")
 		}
@@ -26,33 +46,147 @@ impl<R> Future for Task<R> {
 
 impl<R> Drop for Task<R> {
 	fn drop(&mut self) {
-		self.0.take().expect("`Task` already dropped").detach();
+		if let Some(task) = self.task.take() {
+			if self.cancel_on_drop {
+				drop(task);
+			} else {
+				task.detach();
+			}
+		}
 	}
 }
 
-impl From<async_task::Runnable> for Runnable {
-	fn from(runnable: async_task::Runnable) -> Self {
+impl<R> Task<R> {
+	fn new(task: async_task::Task<R, Metadata>) -> Self {
+		Self {
+			metadata: task.metadata().clone(),
+			task: Some(task.fallible()),
+			cancel_on_drop: false,
+		}
+	}
+
+	/// Returns the [`Metadata`] the task was spawned with.
+	#[must_use]
+	pub const fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Cancels the task and waits for it to stop running. Returns the output if
+	/// the task already finished.
+	pub async fn cancel(mut self) -> Option<R> {
+		self.task
+			.take()
+			.expect("`Task` already dropped")
+			.cancel()
+			.await
+	}
+
+	/// Cancels the task without waiting for it to stop running.
+	pub fn abort(mut self) {
+		drop(self.task.take());
+	}
+
+	/// Lets the task keep running in the background.
+	pub fn detach(mut self) {
+		if let Some(task) = self.task.take() {
+			task.detach();
+		}
+	}
+
+	/// Sets if dropping the [`Task`] cancels it, otherwise it's detached.
+	/// Defaults to `false`.
+	pub fn set_cancel_on_drop(&mut self, cancel: bool) {
+		self.cancel_on_drop = cancel;
+	}
+}
+
+impl From<async_task::Runnable<Metadata>> for Runnable {
+	fn from(runnable: async_task::Runnable<Metadata>) -> Self {
 		Self(runnable)
 	}
 }
 
 impl Runnable {
 	pub fn run(self) {
-		self.0.run();
+		let id = self.0.metadata().id();
+		metadata::enter(id, || self.0.run());
 	}
 
 	pub fn schedule(self) {
 		self.0.schedule();
 	}
+
+	#[must_use]
+	pub fn metadata(&self) -> &Metadata {
+		self.0.metadata()
+	}
 }
 
-pub fn spawn<F, S>(future: F, schedule: S) -> (Runnable, Task<F::Output>)
+pub fn spawn<F, S>(future: F, metadata: Metadata, schedule: S) -> (Runnable, Task<F::Output>)
 where
 	F: Future + Send + 'static,
 	F::Output: Send + 'static,
 	S: Fn(Runnable) + Send + Sync + 'static,
 {
-	let (runnable, task) = async_task::spawn(future, move |runnable| schedule(runnable.into()));
+	let (runnable, task) = async_task::Builder::new().metadata(metadata).spawn(
+		|_| future,
+		move |runnable: async_task::Runnable<Metadata>| {
+			schedule(runnable.into());
+		},
+	);
+
+	(runnable.into(), Task::new(task))
+}
+
+/// Like [`spawn`], but `future` and it's output don't have to be `'static`.
+///
+/// # Safety
+/// `future` has to be dropped, by finishing or being cancelled, before
+/// anything it borrows is. The same applies to it's output, which is dropped
+/// when the [`Task`] is.
+pub unsafe fn spawn_unchecked<F, S>(
+	future: F,
+	metadata: Metadata,
+	schedule: S,
+) -> (Runnable, Task<F::Output>)
+where
+	F: Future + Send,
+	F::Output: Send,
+	S: Fn(Runnable) + Send + Sync + 'static,
+{
+	let (runnable, task) = async_task::Builder::new()
+		.metadata(metadata)
+		.spawn_unchecked(
+			|_| future,
+			move |runnable: async_task::Runnable<Metadata>| {
+				schedule(runnable.into());
+			},
+		);
+
+	(runnable.into(), Task::new(task))
+}
+
+/// Spawns a `!Send` future, which is scheduled on `sender` and can only be
+/// run by the current thread.
+pub fn spawn_local<F>(
+	future: F,
+	metadata: Metadata,
+	sender: LocalSender,
+) -> (LocalRunnable, Task<F::Output>)
+where
+	F: Future + 'static,
+	F::Output: 'static,
+{
+	let scheduler = Scheduler(sender);
+	let (runnable, task) = async_task::Builder::new().metadata(metadata).spawn_local(
+		|_| future,
+		move |runnable| {
+			// the receiving thread is gone, the task can't be run or dropped anymore
+			if let Err(runnable) = scheduler.0.try_send(LocalRunnable::new(runnable)) {
+				mem::forget(runnable);
+			}
+		},
+	);
 
-	(runnable.into(), Task(Some(task)))
+	(LocalRunnable::new(runnable), Task::new(task))
 }
@@ -0,0 +1,810 @@
+use std::{
+	cell::Cell,
+	future::Future,
+	panic,
+	pin::Pin,
+	rc::Rc,
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+	task::{Context, Poll},
+	time::Duration,
+};
+
+use allochronic::{
+	coop, error, task, time, Affinity, Executor, Handle, Idle, StealPolicy, Task, TaskGroup,
+	TaskSet,
+};
+use allochronic_channel::{mpmc, oneshot};
+use futures_util::{future, StreamExt};
+
+#[test]
+fn builder() {
+	let result = Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.thread_name("test-worker")
+		.start(async { Task::spawn(async { 1 }).await });
+
+	assert_eq!(result.expect("task failed"), 1);
+}
+
+#[test]
+fn handle() {
+	let builder = Executor::builder().workers(2).affinity(Affinity::None);
+	// clones share the `Executor` created for the handle
+	let handle = builder.clone().handle();
+	let queued = handle.spawn(async { 1 });
+
+	let result = builder.start(async {
+		let handle = Handle::current();
+		let thread = std::thread::spawn(move || {
+			handle.block_on(async { 2 })
+				+ handle
+					.block_on(handle.spawn(async { 3 }))
+					.expect("task failed")
+		});
+
+		queued.await.expect("task failed") + thread.join().expect("thread panicked")
+	});
+
+	assert_eq!(result, 6);
+}
+
+#[test]
+fn blocking() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.max_blocking_threads(1)
+		.blocking_keep_alive(Duration::from_millis(10))
+		.start(async {
+			let name =
+				allochronic::spawn_blocking(|| std::thread::current().name().map(String::from))
+					.await
+					.expect("task failed");
+			assert_eq!(name.as_deref(), Some("allochronic-worker-blocking"));
+
+			// blocking threads hand tasks to the async workers
+			let value = allochronic::spawn_blocking(|| {
+				Task::block_on(async { Task::spawn(async { 1 }).await.expect("task failed") })
+			})
+			.await
+			.expect("task failed");
+			assert_eq!(value, 1);
+		});
+}
+
+#[test]
+fn blocking_join() {
+	let finished = Arc::new(AtomicBool::new(false));
+
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start({
+			let finished = Arc::clone(&finished);
+
+			async move {
+				let (started, running) = oneshot::oneshot();
+
+				allochronic::spawn_blocking(move || {
+					started.send(());
+					std::thread::sleep(Duration::from_millis(50));
+					finished.store(true, Ordering::SeqCst);
+				})
+				.detach();
+
+				running.await.expect("task cancelled");
+			}
+		});
+
+	// running blocking tasks finish before `start` returns
+	assert!(finished.load(Ordering::SeqCst));
+}
+
+#[test]
+fn blocking_spawn_failure() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		// too large to allocate, spawning blocking threads fails
+		.stack_size(1 << 46)
+		.start(async {
+			let result = allochronic::spawn_blocking(|| 1).await;
+			assert!(matches!(result, Err(error::Join::Cancelled)));
+		});
+}
+
+#[test]
+fn cancel() {
+	Executor::start(async {
+		assert_eq!(Task::spawn(future::pending::<()>()).cancel().await, None);
+
+		let task = Task::spawn(async { 1 });
+		Executor::wait().await;
+		assert_eq!(task.cancel().await, Some(1));
+
+		// waiting would never finish if the task wasn't cancelled
+		drop(Task::spawn(future::pending::<()>()).cancel_on_drop());
+		Executor::wait().await;
+	});
+}
+
+#[test]
+fn priority() {
+	let order = Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let order = Arc::new(Mutex::new(Vec::new()));
+
+			for priority in [u8::MAX, 1, 0] {
+				let order = Arc::clone(&order);
+				Task::spawn_with_priority(priority, async move {
+					order.lock().expect("lock poisoned").push(priority);
+				});
+			}
+
+			Executor::wait().await;
+			Arc::try_unwrap(order)
+				.expect("tasks not finished")
+				.into_inner()
+				.expect("lock poisoned")
+		});
+
+	assert_eq!(order, [0, 1, u8::MAX]);
+}
+
+#[test]
+fn group() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let group = TaskGroup::with_workers([1]);
+
+			let worker = group
+				.spawn(async { std::thread::current().name().map(String::from) })
+				.await
+				.expect("task failed");
+			assert_eq!(worker.as_deref(), Some("allochronic-worker-1"));
+
+			let pending = group.spawn(future::pending::<()>());
+			assert_eq!(group.len(), 1);
+			group.cancel();
+			assert!(matches!(pending.await, Err(error::Join::Cancelled)));
+			assert!(group.is_empty());
+		});
+}
+
+#[test]
+fn group_reuse() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			// dropped groups are removed and their ids reused, workers have to
+			// drop the old queues before serving the new group
+			for round in 0..100 {
+				let worker = round % 2;
+				let group = TaskGroup::with_workers([worker]);
+
+				let name = group
+					.spawn_with_priority(1, async {
+						std::thread::current().name().map(String::from)
+					})
+					.await
+					.expect("task failed");
+				assert_eq!(name.as_deref() == Some("allochronic-worker-1"), worker == 1);
+			}
+		});
+}
+
+#[test]
+fn local() {
+	let counter = Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let counter = Rc::new(Cell::new(0));
+			let thread = std::thread::current().id();
+
+			for _ in 0..10 {
+				let counter = Rc::clone(&counter);
+				Task::spawn_local(async move {
+					assert_eq!(std::thread::current().id(), thread);
+					future::ready(()).await;
+					counter.set(counter.get() + 1);
+				});
+			}
+
+			Executor::wait().await;
+			counter.get()
+		});
+
+	assert_eq!(counter, 10);
+}
+
+#[test]
+fn identity() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let main = task::current_id().expect("no current task");
+
+			let named = Task::builder()
+				.name("named")
+				.spawn(async { task::current_id() });
+			let unnamed = Task::spawn(async { task::current_id() });
+
+			assert_eq!(named.name(), Some("named"));
+			assert_eq!(unnamed.name(), None);
+			assert_ne!(named.id(), unnamed.id());
+			assert_ne!(named.id(), main);
+
+			let (named_id, unnamed_id) = (named.id(), unnamed.id());
+			assert_eq!(named.await.expect("task failed"), Some(named_id));
+			assert_eq!(unnamed.await.expect("task failed"), Some(unnamed_id));
+			assert_eq!(task::current_id(), Some(main));
+		});
+
+	assert_eq!(task::current_id(), None);
+}
+
+allochronic::task_local! {
+	static REQUEST: usize;
+}
+
+#[test]
+fn task_local() {
+	Executor::builder()
+		.workers(4)
+		.affinity(Affinity::None)
+		.start(async {
+			assert_eq!(REQUEST.try_with(|_| ()), Err(error::Access));
+
+			let tasks: Vec<_> = (0..10)
+				.map(|request| {
+					Task::spawn(REQUEST.scope(request, async move {
+						// yielding lets other workers steal the task
+						for _ in 0..100 {
+							futures_lite::future::yield_now().await;
+							assert_eq!(REQUEST.get(), request);
+						}
+					}))
+				})
+				.collect();
+			future::try_join_all(tasks).await.expect("task failed");
+
+			let nested = REQUEST.scope(1, async {
+				REQUEST.scope(2, async { REQUEST.get() }).await + REQUEST.get()
+			});
+			assert_eq!(nested.await, 3);
+			assert_eq!(REQUEST.try_with(|_| ()), Err(error::Access));
+		});
+}
+
+#[test]
+fn task_local_block_on() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			// the only worker runs the task while blocking inside the scope
+			let task = Task::spawn(async { REQUEST.try_with(|request| *request) });
+			let result = REQUEST.scope(7, async { Task::block_on(task) }).await;
+
+			assert_eq!(result.expect("task failed"), Err(error::Access));
+			assert_eq!(REQUEST.try_with(|_| ()), Err(error::Access));
+		});
+}
+
+#[test]
+fn scope() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let mut values = vec![1, 2, 3];
+			let finished = AtomicUsize::new(0);
+
+			let sum = allochronic::scope(|scope| {
+				let (values, finished) = (&values, &finished);
+
+				async move {
+					// detached tasks are waited on too
+					scope
+						.spawn(async move {
+							time::sleep(Duration::from_millis(50)).await;
+							finished.fetch_add(1, Ordering::SeqCst);
+						})
+						.detach();
+
+					let tasks: Vec<_> = values
+						.iter()
+						.map(|value| scope.spawn(async move { value * 2 }))
+						.collect();
+					future::try_join_all(tasks)
+						.await
+						.expect("task failed")
+						.into_iter()
+						.sum::<i32>()
+				}
+			});
+
+			assert_eq!(sum, 12);
+			assert_eq!(finished.load(Ordering::SeqCst), 1);
+			values.push(4);
+		});
+}
+
+#[test]
+fn scope_panic() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let result = panic::catch_unwind(|| {
+				allochronic::scope(|scope| -> future::Ready<()> {
+					// would never finish if not cancelled
+					scope.spawn(future::pending::<()>()).detach();
+					scope.spawn(async {}).detach();
+					panic!("body panicked");
+				})
+			});
+
+			assert!(result.is_err());
+		});
+}
+
+#[test]
+fn set() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let mut set = TaskSet::new();
+
+			for value in (1..=3).rev() {
+				set.spawn(async move {
+					time::sleep(Duration::from_millis(value * 20)).await;
+					value
+				});
+			}
+
+			assert_eq!(set.len(), 3);
+			assert_eq!(
+				set.join_next()
+					.await
+					.expect("set empty")
+					.expect("task failed"),
+				1
+			);
+
+			let rest: Vec<_> = (&mut set)
+				.map(|result| result.expect("task failed"))
+				.collect()
+				.await;
+			assert_eq!(rest, [2, 3]);
+			assert!(set.join_next().await.is_none());
+
+			// aborted and dropped tasks are cancelled
+			let finished = Arc::new(AtomicUsize::new(0));
+
+			let spawn = |set: &mut TaskSet<()>| {
+				for _ in 0..10 {
+					let finished = Arc::clone(&finished);
+					set.spawn(async move {
+						time::sleep(Duration::from_millis(50)).await;
+						finished.fetch_add(1, Ordering::SeqCst);
+					});
+				}
+			};
+
+			let mut aborted = TaskSet::new();
+			spawn(&mut aborted);
+			aborted.abort_all();
+			assert!(aborted.is_empty());
+
+			let mut dropped = TaskSet::new();
+			spawn(&mut dropped);
+			drop(dropped);
+
+			time::sleep(Duration::from_millis(100)).await;
+			assert_eq!(finished.load(Ordering::SeqCst), 0);
+		});
+}
+
+#[test]
+fn wait() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			assert_eq!(Executor::active_tasks(), 0);
+			let mut counts = Executor::task_counts();
+
+			let task = Task::spawn(time::sleep(Duration::from_millis(100)));
+			assert_eq!(Executor::active_tasks(), 1);
+			assert!(!Executor::wait_timeout(Duration::from_millis(10)).await);
+
+			assert!(Executor::wait_timeout(Duration::from_secs(10)).await);
+			assert_eq!(Executor::active_tasks(), 0);
+			task.await.expect("task failed");
+
+			// only the latest count is kept
+			assert_eq!(counts.next().await, Some(0));
+			assert!(allochronic_util::poll(counts.next()).await.is_pending());
+		});
+}
+
+#[test]
+fn wait_concurrent() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let task = Task::spawn(time::sleep(Duration::from_millis(100)));
+
+			// every waiter is woken up when the last task finishes
+			let finished =
+				future::join_all((0..2).map(|_| Executor::wait_timeout(Duration::from_secs(2))))
+					.await;
+			assert_eq!(finished, [true, true]);
+			task.await.expect("task failed");
+		});
+}
+
+#[test]
+fn metrics() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let tasks: Vec<_> = (0..10).map(|_| Task::spawn(async {})).collect();
+			future::try_join_all(tasks).await.expect("task failed");
+
+			let metrics = Executor::metrics();
+			assert_eq!(metrics.spawned, 10);
+			assert_eq!(metrics.alive, 0);
+			assert_eq!(metrics.workers.len(), 2);
+
+			let polled: u64 = metrics.workers.iter().map(|worker| worker.polled).sum();
+			assert!(polled >= 10);
+
+			for worker in &metrics.workers {
+				assert_eq!(
+					worker.polled,
+					worker.local + worker.group + worker.injector + worker.stolen
+				);
+			}
+		});
+}
+
+#[test]
+fn idle() {
+	for idle in [Idle::BusyPoll, Idle::SpinThenPark(16), Idle::Park] {
+		// an `Executor` can only be started once per thread
+		let result = std::thread::spawn(move || {
+			Executor::builder()
+				.workers(4)
+				.affinity(Affinity::None)
+				.idle(idle)
+				.start(async {
+					let tasks: Vec<_> = (0..10)
+						.map(|_| Task::spawn(time::sleep(Duration::from_millis(10))))
+						.collect();
+					future::try_join_all(tasks).await.expect("task failed");
+
+					// spawning from outside of a worker has to wake up a parked one
+					let handle = Handle::current();
+					let task = std::thread::spawn(move || handle.spawn(async { 1 }))
+						.join()
+						.expect("thread panicked");
+
+					task.await.expect("task failed")
+				})
+		})
+		.join()
+		.expect("executor panicked");
+
+		assert_eq!(result, 1, "{:?}", idle);
+	}
+}
+
+#[test]
+fn steal_policy() {
+	for policy in [
+		StealPolicy::Random,
+		StealPolicy::RoundRobin,
+		StealPolicy::NearFirst,
+	] {
+		// an `Executor` can only be started once per thread
+		let stolen = std::thread::spawn(move || {
+			Executor::builder()
+				.workers(4)
+				.affinity(Affinity::None)
+				.steal_policy(policy)
+				.start(async {
+					// a burst of tasks queued on a single worker
+					Task::spawn(async {
+						let tasks: Vec<_> = (0..64)
+							.map(|_| {
+								Task::spawn(async { std::thread::sleep(Duration::from_millis(1)) })
+							})
+							.collect();
+						future::try_join_all(tasks).await
+					})
+					.await
+					.and_then(|result| result)
+					.expect("task failed");
+
+					Executor::metrics()
+						.workers
+						.iter()
+						.map(|worker| worker.stolen)
+						.sum::<u64>()
+				})
+		})
+		.join()
+		.expect("executor panicked");
+
+		assert!(stolen > 0, "{:?}", policy);
+	}
+}
+
+#[test]
+fn lifo_slot() {
+	let order = Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let order = Arc::new(Mutex::new(Vec::new()));
+
+			let (senders, tasks): (Vec<_>, Vec<_>) = (0..3)
+				.map(|index| {
+					let (sender, receiver) = oneshot::oneshot::<()>();
+					let order = Arc::clone(&order);
+
+					let task = Task::spawn(async move {
+						receiver.await.expect("sender dropped");
+						order.lock().expect("lock poisoned").push(index);
+					});
+
+					(sender, task)
+				})
+				.unzip();
+
+			// let all tasks wait for their wake up
+			time::sleep(Duration::from_millis(10)).await;
+
+			for sender in senders {
+				sender.send(());
+			}
+
+			future::try_join_all(tasks).await.expect("task failed");
+
+			Arc::try_unwrap(order)
+				.expect("tasks still alive")
+				.into_inner()
+				.expect("lock poisoned")
+		});
+
+	// the task woken up last is run first, the others in order
+	assert_eq!(order, [2, 0, 1]);
+}
+
+#[test]
+fn lifo_slot_limit() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let stop = Arc::new(AtomicBool::new(false));
+			let (ping, mut pings) = mpmc::unbounded();
+			let (pong, mut pongs) = mpmc::unbounded();
+
+			// both tasks keep waking each other up through the LIFO slot
+			let pinger = Task::spawn({
+				let stop = Arc::clone(&stop);
+
+				async move {
+					while !stop.load(Ordering::SeqCst) {
+						ping.send(());
+						pongs.next().await;
+					}
+				}
+			});
+			let ponger = Task::spawn(async move {
+				while pings.next().await.is_some() {
+					// the pinger might have stopped already
+					let _result = pong.try_send(());
+				}
+			});
+			// queued behind them, only runs if the slot is limited
+			let stopper = Task::spawn(async move { stop.store(true, Ordering::SeqCst) });
+
+			future::try_join3(pinger, ponger, stopper)
+				.await
+				.expect("task failed");
+		});
+}
+
+#[test]
+fn coop() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let stop = Arc::new(AtomicBool::new(false));
+
+			// neither task ever waits, only the budget makes them yield
+			let channel = Task::spawn({
+				let stop = Arc::clone(&stop);
+
+				async move {
+					let (sender, mut receiver) = mpmc::unbounded();
+					sender.send(());
+
+					while !stop.load(Ordering::SeqCst) {
+						receiver.next().await;
+						sender.send(());
+					}
+				}
+			});
+			let budget = Task::spawn({
+				let stop = Arc::clone(&stop);
+
+				async move {
+					while !stop.load(Ordering::SeqCst) {
+						coop::consume_budget().await;
+					}
+				}
+			});
+			let stopper = Task::spawn(async move { stop.store(true, Ordering::SeqCst) });
+
+			future::try_join3(channel, budget, stopper)
+				.await
+				.expect("task failed");
+		});
+}
+
+#[test]
+fn injector_interval() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.injector_interval(7)
+		.start(async {
+			let stop = Arc::new(AtomicBool::new(false));
+
+			// keeps the local queue from ever running empty
+			let local = Task::spawn({
+				let stop = Arc::clone(&stop);
+
+				async move {
+					while !stop.load(Ordering::SeqCst) {
+						allochronic_util::r#yield().await;
+					}
+				}
+			});
+
+			// spawned from outside of workers, only reachable through the injector
+			let handle = Handle::current();
+			let injected = std::thread::spawn(move || {
+				handle.spawn(async move { stop.store(true, Ordering::SeqCst) })
+			})
+			.join()
+			.expect("thread panicked");
+
+			future::try_join(local, injected)
+				.await
+				.expect("task failed");
+		});
+}
+
+#[test]
+fn shutdown() {
+	let result = Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.try_start(async {
+			Task::spawn(future::pending::<()>());
+			assert_eq!(Executor::shutdown(), 1);
+			future::pending::<()>().await;
+		});
+
+	assert!(matches!(result, Err(error::Executor::Cancelled)));
+}
+
+#[test]
+fn shutdown_graceful() {
+	let cancelled = Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let task = Task::spawn(async {
+				for _ in 0..3 {
+					allochronic_util::r#yield().await;
+				}
+
+				1
+			});
+			Task::spawn(future::pending::<()>());
+
+			let cancelled = Executor::shutdown_graceful(Duration::from_millis(100)).await;
+			assert_eq!(task.await.expect("task failed"), 1);
+
+			cancelled
+		});
+
+	assert_eq!(cancelled, 1);
+}
+
+#[test]
+fn task_panic() {
+	let result = Executor::start(async { Task::spawn(async { panic!("task panic") }).await });
+
+	if let Err(error::Join::Panic(payload)) = result {
+		assert_eq!(payload.downcast_ref::<&str>(), Some(&"task panic"));
+	} else {
+		panic!("expected a task panic");
+	}
+}
+
+#[test]
+fn worker_panic() {
+	/// Panics are caught when dropping the future or output too.
+	struct PanicOnDrop(Arc<AtomicUsize>);
+
+	/// Finishes immediately, but panics when dropped.
+	struct Finished {
+		_panic: PanicOnDrop,
+	}
+
+	impl Future for Finished {
+		type Output = ();
+
+		fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+			Poll::Ready(())
+		}
+	}
+
+	impl Drop for PanicOnDrop {
+		fn drop(&mut self) {
+			self.0.fetch_add(1, Ordering::SeqCst);
+			panic!("worker panic");
+		}
+	}
+
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.try_start(async {
+			let dropped = Arc::new(AtomicUsize::new(0));
+			Task::spawn(Finished {
+				_panic: PanicOnDrop(Arc::clone(&dropped)),
+			});
+			// the output of a detached task is dropped by the worker
+			Task::spawn({
+				let dropped = Arc::clone(&dropped);
+				async move { PanicOnDrop(dropped) }
+			});
+			// block the main worker so the other worker has to steal the tasks
+			std::thread::sleep(Duration::from_millis(100));
+			assert_eq!(dropped.load(Ordering::SeqCst), 2);
+
+			// the other worker is still running tasks
+			let ran = Arc::new(AtomicBool::new(false));
+			Task::spawn({
+				let ran = Arc::clone(&ran);
+				async move { ran.store(true, Ordering::SeqCst) }
+			});
+			std::thread::sleep(Duration::from_millis(100));
+			assert!(ran.load(Ordering::SeqCst));
+		})
+		.expect("worker panicked");
+}
@@ -0,0 +1,123 @@
+use std::{sync::Arc, time::Duration};
+
+use allochronic::{
+	net::{TcpListener, TcpStream, UdpSocket},
+	time, Affinity, Executor, Task,
+};
+use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+
+#[test]
+fn tcp() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let listener = TcpListener::bind("127.0.0.1:0".parse().expect("invalid address"))
+				.expect("bind failed");
+			let addr = listener.local_addr().expect("no local address");
+
+			let server = Task::spawn(async move {
+				let (mut stream, _) = listener.accept().await.expect("accept failed");
+				let mut buf = Vec::new();
+				stream.read_to_end(&mut buf).await.expect("read failed");
+				stream.write_all(&buf).await.expect("write failed");
+			});
+
+			let mut stream = TcpStream::connect(addr).await.expect("connect failed");
+			stream.write_all(b"hello").await.expect("write failed");
+			stream.close().await.expect("close failed");
+
+			let mut buf = Vec::new();
+			stream.read_to_end(&mut buf).await.expect("read failed");
+			assert_eq!(buf, b"hello");
+
+			server.await.expect("server failed");
+		});
+}
+
+#[test]
+fn udp() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let addr = "127.0.0.1:0".parse().expect("invalid address");
+			let first = UdpSocket::bind(addr).expect("bind failed");
+			let second = UdpSocket::bind(addr).expect("bind failed");
+			let second_addr = second.local_addr().expect("no local address");
+
+			let receiver = Task::spawn(async move {
+				let mut buf = [0; 5];
+				let (len, _) = second.recv_from(&mut buf).await.expect("receive failed");
+				buf[..len].to_vec()
+			});
+
+			first
+				.send_to(b"hello", second_addr)
+				.await
+				.expect("send failed");
+			assert_eq!(receiver.await.expect("receiver failed"), b"hello");
+		});
+}
+
+#[test]
+fn udp_readers() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let addr = "127.0.0.1:0".parse().expect("invalid address");
+			let first = UdpSocket::bind(addr).expect("bind failed");
+			let second = Arc::new(UdpSocket::bind(addr).expect("bind failed"));
+			let second_addr = second.local_addr().expect("no local address");
+
+			let readers: Vec<_> = (0..2)
+				.map(|_| {
+					let second = Arc::clone(&second);
+
+					Task::spawn(async move {
+						let mut buf = [0; 5];
+						let (len, _) = second.recv_from(&mut buf).await.expect("receive failed");
+						buf[..len].to_vec()
+					})
+				})
+				.collect();
+
+			// let both readers wait for the socket
+			for _ in 0..10 {
+				future::yield_now().await;
+			}
+
+			for _ in 0..2 {
+				first
+					.send_to(b"hello", second_addr)
+					.await
+					.expect("send failed");
+			}
+
+			for reader in readers {
+				let received = time::timeout(Duration::from_secs(2), reader)
+					.await
+					.expect("reader wasn't woken up");
+				assert_eq!(received.expect("reader failed"), b"hello");
+			}
+		});
+}
+
+#[cfg(unix)]
+#[test]
+fn unix() {
+	use allochronic::net::UnixStream;
+
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let (mut first, mut second) = UnixStream::pair().expect("pair failed");
+
+			first.write_all(b"hello").await.expect("write failed");
+			let mut buf = [0; 5];
+			second.read_exact(&mut buf).await.expect("read failed");
+			assert_eq!(&buf, b"hello");
+		});
+}
@@ -0,0 +1,99 @@
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+use allochronic::{error, time, Affinity, Executor, Task};
+use futures_util::{future, StreamExt};
+
+#[test]
+fn sleep() {
+	Executor::builder()
+		.workers(2)
+		.affinity(Affinity::None)
+		.start(async {
+			let start = Instant::now();
+			time::sleep(Duration::from_millis(50)).await;
+			assert!(start.elapsed() >= Duration::from_millis(50));
+
+			// earlier timers registered after later ones still fire in time
+			let start = Instant::now();
+			let late = Task::spawn(time::sleep(Duration::from_secs(10)));
+			Task::spawn(time::sleep(Duration::from_millis(50)))
+				.await
+				.expect("task failed");
+			assert!(start.elapsed() < Duration::from_secs(10));
+			late.abort();
+		});
+}
+
+#[test]
+fn sleep_busy() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let stop = Arc::new(AtomicBool::new(false));
+
+			// keeps the only worker from ever running out of tasks
+			let busy = Task::spawn({
+				let stop = Arc::clone(&stop);
+
+				async move {
+					while !stop.load(Ordering::SeqCst) {
+						allochronic_util::r#yield().await;
+					}
+				}
+			});
+
+			let start = Instant::now();
+			time::sleep(Duration::from_millis(20)).await;
+			assert!(start.elapsed() < Duration::from_secs(1));
+
+			stop.store(true, Ordering::SeqCst);
+			busy.await.expect("task failed");
+		});
+}
+
+#[test]
+fn timeout() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			assert_eq!(
+				time::timeout(Duration::from_millis(50), async { 1 }).await,
+				Ok(1)
+			);
+			assert_eq!(
+				time::timeout(Duration::from_millis(50), future::pending::<()>()).await,
+				Err(error::Elapsed)
+			);
+			// too large to represent, never elapses
+			assert_eq!(
+				time::timeout(Duration::from_millis(50), time::sleep(Duration::MAX)).await,
+				Err(error::Elapsed)
+			);
+		});
+}
+
+#[test]
+fn interval() {
+	Executor::builder()
+		.workers(1)
+		.affinity(Affinity::None)
+		.start(async {
+			let start = Instant::now();
+			let ticks: Vec<_> = time::interval(Duration::from_millis(20))
+				.take(3)
+				.collect()
+				.await;
+
+			assert_eq!(ticks.len(), 3);
+			assert!(ticks[2] - ticks[0] >= Duration::from_millis(40));
+			assert!(start.elapsed() >= Duration::from_millis(40));
+		});
+}
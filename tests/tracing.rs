@@ -0,0 +1,96 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+	fmt::Debug,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+};
+
+use allochronic::{Affinity, Executor, Task};
+use tracing::{
+	field::{Field, Visit},
+	span::{Attributes, Id, Record},
+	Event, Metadata, Subscriber,
+};
+
+/// Records the fields of all `task` spans.
+#[derive(Default)]
+struct Spans {
+	next: AtomicU64,
+	tasks: Arc<Mutex<Vec<Fields>>>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Fields {
+	id: Option<u64>,
+	name: Option<String>,
+}
+
+impl Visit for Fields {
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		if field.name() == "id" {
+			self.id = Some(value);
+		}
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		if field.name() == "name" {
+			self.name = Some(String::from(value));
+		}
+	}
+
+	fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+impl Subscriber for Spans {
+	fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+		true
+	}
+
+	fn new_span(&self, span: &Attributes<'_>) -> Id {
+		if span.metadata().name() == "task" {
+			let mut fields = Fields::default();
+			span.record(&mut fields);
+			self.tasks.lock().expect("lock poisoned").push(fields);
+		}
+
+		Id::from_u64(self.next.fetch_add(1, Ordering::Relaxed) + 1)
+	}
+
+	fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+	fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+	fn event(&self, _event: &Event<'_>) {}
+
+	fn enter(&self, _span: &Id) {}
+
+	fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn task_span() {
+	let spans = Spans::default();
+	let tasks = Arc::clone(&spans.tasks);
+
+	// spans of spawned tasks are created on the spawning thread
+	let id = tracing::subscriber::with_default(spans, || {
+		Executor::builder()
+			.workers(1)
+			.affinity(Affinity::None)
+			.start(async {
+				let task = Task::builder().name("named").spawn(async {});
+				let id = task.id();
+				task.await.expect("task failed");
+
+				id
+			})
+	});
+
+	assert!(tasks.lock().expect("lock poisoned").contains(&Fields {
+		id: Some(id.to_string().parse().expect("invalid id")),
+		name: Some(String::from("named")),
+	}));
+}
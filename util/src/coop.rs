@@ -0,0 +1,99 @@
+//! Cooperative scheduling budget, see [`budget`] and [`poll_proceed`].
+
+use std::{
+	cell::Cell,
+	task::{Context, Poll},
+};
+
+thread_local!(static BUDGET: Cell<Option<u8>> = Cell::new(None));
+
+/// Restores the previous budget when dropped, even when unwinding.
+struct Reset(Option<u8>);
+
+impl Drop for Reset {
+	fn drop(&mut self) {
+		BUDGET.with(|budget| budget.set(self.0));
+	}
+}
+
+/// Runs `function` with a budget of `budget` operations, after which
+/// [`poll_proceed`] makes the current task yield. Executors call this around
+/// polling a task.
+///
+/// # Examples
+/// ```
+/// use allochronic_util::coop;
+///
+/// assert!(coop::has_remaining());
+/// coop::budget(0, || assert!(!coop::has_remaining()));
+/// assert!(coop::has_remaining());
+/// ```
+pub fn budget<R, F: FnOnce() -> R>(budget: u8, function: F) -> R {
+	with(Some(budget), function)
+}
+
+/// Runs `function` without a budget, [`poll_proceed`] always proceeds.
+///
+/// # Examples
+/// ```
+/// use allochronic_util::coop;
+///
+/// coop::budget(0, || {
+/// 	coop::unconstrained(|| assert!(coop::has_remaining()));
+/// });
+/// ```
+pub fn unconstrained<R, F: FnOnce() -> R>(function: F) -> R {
+	with(None, function)
+}
+
+fn with<R, F: FnOnce() -> R>(budget: Option<u8>, function: F) -> R {
+	let _reset = Reset(BUDGET.with(|current| current.replace(budget)));
+	function()
+}
+
+/// Consumes one operation of the budget. If it's exhausted, wakes the
+/// current task and returns [`Poll::Pending`] to yield to the executor.
+///
+/// Operations that turn out to not be ready should give it back with
+/// [`refund`].
+///
+/// # Examples
+/// ```
+/// # futures_executor::block_on(async {
+/// use std::task::Poll;
+///
+/// use allochronic_util::{coop, poll};
+///
+/// let mut future = futures_util::future::poll_fn(|cx| coop::budget(0, || coop::poll_proceed(cx)));
+/// assert_eq!(Poll::Pending, poll(&mut future).await);
+/// # });
+/// ```
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+	BUDGET.with(|budget| match budget.get() {
+		Some(0) => {
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		}
+		Some(remaining) => {
+			budget.set(Some(remaining - 1));
+			Poll::Ready(())
+		}
+		None => Poll::Ready(()),
+	})
+}
+
+/// Gives back an operation consumed by [`poll_proceed`] that didn't make
+/// progress.
+pub fn refund() {
+	BUDGET.with(|budget| {
+		if let Some(remaining) = budget.get() {
+			budget.set(Some(remaining.saturating_add(1)));
+		}
+	});
+}
+
+/// Returns `true` if the current task has budget left or isn't constrained.
+#[must_use]
+pub fn has_remaining() -> bool {
+	BUDGET.with(|budget| budget.get() != Some(0))
+}
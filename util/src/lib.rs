@@ -53,6 +53,7 @@
 
 //! TODO
 
+pub mod coop;
 mod poll_once;
 #[doc(hidden)]
 pub mod select;